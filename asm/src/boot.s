@@ -4,6 +4,9 @@
 ; - 2.5.3.2 Effective-Address Computation
 ; - 3.5.2.3 Executing a Loop or Repeat Zero Times
 
+; Multiboot2 Specification version 2.0
+; - 3.3 I386 machine state
+
 ; NASM - The Netwide Assembler
 ; - 3.2.2 RESB and Friends: Declaring Uninitialized Data
 ; - 3.2.4 EQU: Defining Constants
@@ -17,6 +20,9 @@ bits 32
 [warning -reloc-rel-dword]                    ; 32-bit relative section-crossing relocation
 
 global _start
+global stack_guard                            ; Left unmapped once paging is enabled
+global stack_bottom                           ; Reserved by the frame allocator
+global stack_top
 extern rust_main
 
 absolute 0x000b8000                           ; VGA memory-mapped I/O
@@ -36,9 +42,10 @@ str_len: equ $ - str
 section .text
 _start:
 	mov esp, stack_top
-	call put_str                              ; Call Procedure
+	call put_str                              ; Call Procedure (preserves EAX & EBX)
 	mov dword [screen_1 + 0x0f9c], 0x00320034 ; Print '42' to screen (bottom right)
-	push 42;
+	push ebx                                  ; Multiboot2 boot information physical address
+	push eax                                  ; Multiboot2 bootloader magic value
 	call rust_main
 	hlt                                       ; Halt
 
@@ -53,6 +60,9 @@ put_str:                                      ; -> Max string length = 80x25 byt
 	ret                                       ; Return from Procedure
 
 section .bss
+alignb 4096
+stack_guard:                                  ; An overflowing stack page faults here
+	resb 4096
 stack_bottom:
 	resb 4096*4
 stack_top:
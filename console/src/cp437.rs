@@ -0,0 +1,42 @@
+// https://en.wikipedia.org/wiki/Code_page_437
+
+/// The characters of bytes 0x80 to 0xFF, the only ones outside ASCII the VGA
+/// font can display (bytes below 0x20 have glyphs too, but are control
+/// characters for us).
+const UPPER_HALF: [char; 128] = [
+	'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å',
+	'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ',
+	'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»',
+	'░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐',
+	'└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧',
+	'╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀',
+	'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩',
+	'≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{a0}',
+];
+
+/// Byte displaying `c`: itself for ASCII, a block when the font lacks it.
+pub fn encode(c: char) -> u8 {
+	if c.is_ascii() {
+		c as u8
+	}
+	else {
+		match UPPER_HALF.iter().position(|&upper| upper == c) {
+			Some(index) => 0x80 + index as u8,
+			None => b'\xfe',
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn encode_maps_latin_letters_and_falls_back_to_a_block() {
+		assert_eq!(b'e', encode('e'));
+		assert_eq!(0x82, encode('é'));
+		assert_eq!(0x9A, encode('Ü'));
+		assert_eq!(0xE1, encode('ß'));
+		assert_eq!(b'\xfe', encode('€'));
+	}
+}
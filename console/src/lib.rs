@@ -0,0 +1,31 @@
+#![cfg_attr(not(test), no_std)]
+
+//! The text console core: a grid of cells with scrolling history and line
+//! editing, independent of where the grid lives (the VGA text buffer in the
+//! kernel, plain memory in host tests).
+
+pub mod cp437;
+pub mod screen;
+
+#[allow(dead_code)]
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Color {
+	#[default]
+	Black = 0,
+	Blue = 1,
+	Green = 2,
+	Cyan = 3,
+	Red = 4,
+	Magenta = 5,
+	Yellow = 6,
+	White = 7,
+	Black_on_White = 0x70,
+	Blue_on_White = 0x71,
+	Green_on_White = 0x72,
+	Cyan_on_White = 0x73,
+	Red_on_White = 0x74,
+	Magenta_on_White = 0x75,
+	Yellow_on_White = 0x76,
+	White_on_White = 0x77,
+}
@@ -1,11 +1,20 @@
 
-use super::Color;
+use core::ops::DerefMut;
+use crate::Color;
+use crate::cp437;
 
 const HISTORY_CAPACITY: usize = 5;
 
+/// Size of a screen, in cells.
+pub const HEIGHT: usize = 25;
+pub const WIDTH: usize = 80;
+
+/// The cells of a screen, laid out like the VGA text buffer.
+pub type Grid = [Row<WIDTH>; HEIGHT];
+
 /// Invariants
-/// 0 <= self.cursor.row < Self::HEIGHT
-/// 0 <= self.cursor.column < Self::WIDTH
+/// 0 <= self.cursor.row < HEIGHT
+/// 0 <= self.cursor.column < WIDTH
 /// for each row, &self.buff[self.cursor.row][0..self.cursor.column] does not contain any b'\0'
 /// last column of the last row (either in self.buff or in self.history) == b'\0'
 
@@ -15,7 +24,7 @@ const HISTORY_CAPACITY: usize = 5;
 
 #[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
 #[repr(C)]
-struct Cell(u8, Color);
+pub struct Cell(u8, Color);
 
 impl Cell {
 	// Traits and Generics -> Fully Qualified Method Calls
@@ -46,7 +55,7 @@ struct Cursor {
 // Structs -> Tuple-Like Structs
 #[derive(Debug, Copy, Clone)]
 #[repr(transparent)]
-struct Row<const W: usize>([Cell; W]);
+pub struct Row<const W: usize>([Cell; W]);
 
 impl<const W: usize> Row<W> {
 	// https://doc.rust-lang.org/core/primitive.slice.html#method.copy_from_slice
@@ -108,7 +117,7 @@ struct History<const N: usize> {
 	head: usize,
 	length: usize,
 	pivot: usize,
-	circular_buffer: [Row<{Screen::WIDTH}>; N],
+	circular_buffer: [Row<WIDTH>; N],
 }
 
 // | oldest upward -- (head)
@@ -125,7 +134,7 @@ impl<const N: usize> History<N> {
 			head: 0,
 			length: 0,
 			pivot: 0,
-			circular_buffer: [Row::<{Screen::WIDTH}>::default(); N],
+			circular_buffer: [Row::<WIDTH>::default(); N],
 		}
 	}
 
@@ -147,9 +156,9 @@ impl<const N: usize> History<N> {
     }
 
 	// This method swaps the content of 'upper_row' with the content of the newest downward row, if any
-	fn push_upper_row(&mut self, upper_row: &mut Row<{Screen::WIDTH}>) -> () {
+	fn push_upper_row(&mut self, upper_row: &mut Row<WIDTH>) -> () {
 		if 0 < N {
-			let dest_row: &mut Row<{Screen::WIDTH}> = &mut self.circular_buffer[(self.head + self.pivot) % N];
+			let dest_row: &mut Row<WIDTH> = &mut self.circular_buffer[(self.head + self.pivot) % N];
 			core::mem::swap(dest_row, upper_row);
 			if self.pivot < self.length {
 				self.pivot += 1;
@@ -167,14 +176,44 @@ impl<const N: usize> History<N> {
 	}
 
 	// If there is a upward history
-	fn pop_upper_row(&mut self, lower_row: &mut Row<{Screen::WIDTH}>) -> () {
+	fn pop_upper_row(&mut self, lower_row: &mut Row<WIDTH>) -> () {
 		if 0 < self.pivot {
 			self.pivot -=1;
-			let src_row: &mut Row<{Screen::WIDTH}> = &mut self.circular_buffer[(self.head + self.pivot) % N];
+			let src_row: &mut Row<WIDTH> = &mut self.circular_buffer[(self.head + self.pivot) % N];
 			core::mem::swap(lower_row, src_row);
 		}
 	}
 
+	// The intent of this method is to left shift downward history
+	// when a cell is removed from the screen latest row, which line
+	// goes on in History: returns the cell to append to that row
+	fn shift_leftward(&mut self) -> Cell {
+		let mut below_start_of_line = Cell::default();
+		if self.pivot < self.length {
+			below_start_of_line = self.circular_buffer[(self.head + self.pivot) % N][0];
+			let mut idx = self.pivot;
+			let mut current_end_of_line = Cell(b'\x01', Color::default());
+			while b'\0' != current_end_of_line.0 && idx < self.length {
+				let next_start_of_line = if idx + 1 < self.length {
+					self.circular_buffer[(self.head + idx + 1) % N][0]
+				}
+				else {
+					Cell::default()
+				};
+				let row: &mut Row<WIDTH> = &mut self.circular_buffer[(self.head + idx) % N];
+				current_end_of_line = row[WIDTH - 1];
+				row.left_shift(0, 1);
+				row[WIDTH - 1] = if b'\0' != current_end_of_line.0 { next_start_of_line } else { Cell::default() };
+				idx += 1;
+			}
+			// Drop the last downward row if the line no longer reaches it
+			if idx == self.length && b'\0' == self.circular_buffer[(self.head + self.length - 1) % N][0].0 {
+				self.length -= 1;
+			}
+		}
+		below_start_of_line
+	}
+
 	// The intent of this method is to right shift downward history
 	// to insert the screen latest cell in the event of a writing on Screen
 	// The caller is responsible for ensuring there is enough room in History
@@ -211,24 +250,22 @@ impl<const N: usize> History<N> {
 // #                              SCREEN                                      #
 // ############################################################################
 
+/// A screen drawn into `B`, e.g. `&'static mut Grid` for the VGA text buffer.
 #[derive(Debug)]
-pub(super) struct Screen {
+pub struct Screen<B> {
 	cursor: Cursor,
 	prompt: Cursor,
 	color: Color,
 	history: History<HISTORY_CAPACITY>,
-	buff: &'static mut [Row<{Self::WIDTH}>; Self::HEIGHT],
-	raw_buff: &'static [u8; Self::SIZE],
+	buff: B,
 	input_mode: bool,
 }
 
-impl Screen {
-	const HEIGHT: usize = 25;
-	const WIDTH: usize = 80;
-	pub const LENGTH: usize = Self::HEIGHT * Self::WIDTH;
+impl<B: DerefMut<Target = Grid>> Screen<B> {
+	pub const LENGTH: usize = HEIGHT * WIDTH;
 	pub const SIZE: usize = Self::LENGTH * core::mem::size_of::<Cell>();
 
-	pub fn new(addr: usize) -> Self {
+	pub fn new(buff: B) -> Self {
 		let mut instance = Self {
 			cursor: Cursor {
 				row: 0,
@@ -240,8 +277,7 @@ impl Screen {
 			},
 			color: Color::default(),
 			history: History::new(),
-			buff: unsafe { &mut (*(addr as *mut _)) },
-			raw_buff: unsafe { & (*(addr as *const _)) },
+			buff,
 			input_mode: false,
 		};
 		instance.initialize();
@@ -303,7 +339,7 @@ impl Screen {
 	}
 
 	pub fn clear(&mut self) -> () {
-		for _ in 0..Self::HEIGHT {
+		for _ in 0..HEIGHT {
 			self.shift_upward();
 		}
 		self.history.clear(); // ?
@@ -328,7 +364,7 @@ impl Screen {
 
 	fn write_new_line(&mut self) -> () {
 		self.cursor.column = 0;
-		if self.cursor.row + 1 < Self::HEIGHT {
+		if self.cursor.row + 1 < HEIGHT {
 			self.cursor.row += 1;
 		}
 		else {
@@ -340,7 +376,7 @@ impl Screen {
 		self.shift_rightward(self.cursor.row, self.cursor.column);
 		Cell::volatile_copy(&mut self.buff[self.cursor.row][self.cursor.column], &Cell(c, self.color));
 		self.cursor.column += 1;
-		if Self::WIDTH == self.cursor.column {
+		if WIDTH == self.cursor.column {
 			self.write_new_line();
 		}
 	}
@@ -349,55 +385,35 @@ impl Screen {
 	// Collections -> Vec<T> -> Splitting
 	// https://doc.rust-lang.org/stable/core/primitive.slice.html#method.chunks_mut
 	fn shift_leftward(&mut self, mut row: usize, column: usize) -> () {
-		// TODO: fix invariants
-		let mut current_end_of_line = Cell::new_from(&self.buff[row][Self::WIDTH - 1]);
+		let mut current_end_of_line = Cell::new_from(&self.buff[row][WIDTH - 1]);
 		self.buff[row].left_shift(column, 1);
-		while b'\0' != current_end_of_line.0 && row + 1 < Self::HEIGHT {
+		// While the line goes on in the row underneath, pull its first cell
+		// up, then shift that row from its very first column
+		while b'\0' != current_end_of_line.0 && row + 1 < HEIGHT {
 			let below_start_of_line = Cell::new_from(&self.buff[row + 1][0]);
-			Cell::volatile_copy(&mut self.buff[row][Self::WIDTH - 1], &below_start_of_line);
+			Cell::volatile_copy(&mut self.buff[row][WIDTH - 1], &below_start_of_line);
 			row += 1;
-			Cell::volatile_copy(&mut current_end_of_line, &self.buff[row][Self::WIDTH - 1]);
-			self.buff[row].left_shift(column, 1);
+			Cell::volatile_copy(&mut current_end_of_line, &self.buff[row][WIDTH - 1]);
+			self.buff[row].left_shift(0, 1);
 		}
 		if b'\0' == current_end_of_line.0 {
-			Cell::volatile_copy(&mut self.buff[row][Self::WIDTH - 1], &Cell::default());
+			Cell::volatile_copy(&mut self.buff[row][WIDTH - 1], &Cell::default());
 		}
 		else {
-			// TODO: fix this
+			// The line goes on in the downward history
+			let below_start_of_line = self.history.shift_leftward();
+			Cell::volatile_copy(&mut self.buff[row][WIDTH - 1], &below_start_of_line);
 		}
-
-
-
-		// let mut it_rows = self.buff.iter_mut().skip(row).peekable();
-		// while let Some(above) = it_rows.next() {
-		// 	let mut it_columns = above.iter_mut().skip(column).peekable();
-		// 	column = 0;
-		// 	while let Some(current) = it_columns.next() {
-		// 		let mut next: &Cell = &Cell::default();
-		// 		// Same row, rightward character
-		// 		if let Some(rightward) = it_columns.peek() {
-		// 			next = rightward;
-		// 		}
-		// 		// Underneath row, first character
-		// 		else if let Some(below) = it_rows.peek() {
-		// 			next = below.first().unwrap();
-		// 		}
-		// 		// References -> Working with References -> Comparing References
-		// 		if current != next {
-		// 			Cell::volatile_copy(current, next);
-		// 		}
-		// 	}
-		// }
 	}
 
 	// Iterators -> Implementing Your Own Iterators
-	// .rev().take(Self::HEIGHT - row).peekable()
+	// .rev().take(HEIGHT - row).peekable()
 	// !(b'\0' == leftward.0)
 	fn shift_rightward(&mut self, mut row: usize, column: usize) -> () {
 		self.buff[row].right_shift(column, 1);
 		let mut above_end_of_line = Cell::new_from(&self.buff[row][column]);
 		// Cell::volatile_copy(&mut self.buff[row][column], Cell::default());
-		while b'\0' != above_end_of_line.0 && row + 1 < Self::HEIGHT {
+		while b'\0' != above_end_of_line.0 && row + 1 < HEIGHT {
 			row += 1;
 			self.buff[row].right_shift(0, 1);
 			above_end_of_line = core::mem::replace(&mut self.buff[row][0], above_end_of_line);
@@ -445,7 +461,7 @@ impl Screen {
 	}
 
 	fn move_cursor_down(&mut self) -> () {
-		if self.cursor.row + 1 < Self::HEIGHT {
+		if self.cursor.row + 1 < HEIGHT {
 			self.cursor.row += 1;
 		}
 		else if 0 < self.history.get_downward_length() {
@@ -455,9 +471,9 @@ impl Screen {
 	}
 
 	fn move_cursor_right(&mut self) -> () {
-		if self.cursor.column + 1 < Self::WIDTH {
+		if self.cursor.column + 1 < WIDTH {
 			if b'\0' == self.buff[self.cursor.row][self.cursor.column].0 {
-				if self.cursor.row + 1 < Self::HEIGHT || 0 < self.history.get_downward_length() {
+				if self.cursor.row + 1 < HEIGHT || 0 < self.history.get_downward_length() {
 					self.cursor.column = 0;
 					self.move_cursor_down();
 				}
@@ -466,7 +482,7 @@ impl Screen {
 				self.cursor.column += 1;
 			}
 		}
-		else if self.cursor.row + 1 < Self::HEIGHT {
+		else if self.cursor.row + 1 < HEIGHT {
 			self.cursor.row += 1;
 			self.cursor.column = 0;
 		}
@@ -482,11 +498,11 @@ impl Screen {
 		}
 		else if 0 < self.cursor.row {
 			self.cursor.row -= 1;
-			self.cursor.column = Self::WIDTH - 1;
+			self.cursor.column = WIDTH - 1;
 		}
 		else if 0 < self.history.get_upward_length() {
 			self.shift_downward();
-			self.cursor.column = Self::WIDTH - 1;
+			self.cursor.column = WIDTH - 1;
 		}
 		self.left_align_cursor();
 	}
@@ -496,21 +512,23 @@ impl Screen {
 		let mut opt: Option<&[u8]> = None;
 		if 0 == self.cursor.row {
 			let mut length: usize = 0;
-			while (length < Self::WIDTH) && (b'\0' != self.buff[0][length].0) {
+			while (length < WIDTH) && (b'\0' != self.buff[0][length].0) {
 				length += 1;
 			}
-			opt = Some(&self.raw_buff[0..(length * core::mem::size_of::<Cell>())]);
+			// Cells are (character, color) byte pairs, as in the VGA text buffer
+			let cells: &[Cell] = &self.buff[0].0[0..length];
+			opt = Some(unsafe { core::slice::from_raw_parts(cells.as_ptr() as *const u8, core::mem::size_of_val(cells)) });
 		}
 		opt
 	}
 }
 
-impl core::fmt::Write for Screen {
+impl<B: DerefMut<Target = Grid>> core::fmt::Write for Screen<B> {
 	fn write_str(&mut self, s: &str) -> core::fmt::Result {
 		self.buff[self.cursor.row][self.cursor.column].1 = Color::default();
 		if self.input_mode {
-			for c in s.bytes() {
-				if c.is_ascii_graphic() {
+			for c in s.chars().map(cp437::encode) {
+				if c.is_ascii_graphic() || !c.is_ascii() {
 					self.write_byte(c);
 				}
 				else if b'\n' == c {
@@ -544,7 +562,7 @@ impl core::fmt::Write for Screen {
 					// scroll up
 					if 0 < self.history.get_upward_length() {
 						self.shift_downward();
-						if self.cursor.row + 1 < Self::HEIGHT {
+						if self.cursor.row + 1 < HEIGHT {
 							self.cursor.row += 1;
 						}
 						self.left_align_cursor();
@@ -570,8 +588,8 @@ impl core::fmt::Write for Screen {
 			}
 		}
 		else {
-			for c in s.bytes() {
-				if c.is_ascii_graphic() {
+			for c in s.chars().map(cp437::encode) {
+				if c.is_ascii_graphic() || !c.is_ascii() {
 					self.write_byte(c);
 				}
 				else if b'\n' == c {
@@ -591,3 +609,151 @@ impl core::fmt::Write for Screen {
 	}
 }
 
+
+#[cfg(test)]
+mod tests {
+	use core::fmt::Write;
+	use super::*;
+
+	fn new_screen() -> Screen<Box<Grid>> {
+		Screen::new(Box::new([Row::default(); HEIGHT]))
+	}
+
+	fn text(row: &Row<WIDTH>) -> String {
+		row.0.iter().take_while(|cell| b'\0' != cell.0).map(|cell| cell.0 as char).collect()
+	}
+
+	fn input(screen: &mut Screen<Box<Grid>>, s: &str) -> () {
+		screen.set_input_mode(true);
+		screen.write_str(s).unwrap();
+		screen.set_input_mode(false);
+	}
+
+	/// Writes `count` lines, numbered from 0.
+	fn write_lines(screen: &mut Screen<Box<Grid>>, count: usize) -> () {
+		for i in 0..count {
+			writeln!(screen, "{i}").unwrap();
+		}
+	}
+
+	#[test]
+	fn write_wraps_at_end_of_row() {
+		let mut screen = new_screen();
+		screen.write_str(&"a".repeat(WIDTH + 5)).unwrap();
+		assert_eq!("a".repeat(WIDTH), text(&screen.buff[0]));
+		assert_eq!("aaaaa", text(&screen.buff[1]));
+		assert_eq!(Cursor { row: 1, column: 5 }, screen.cursor);
+	}
+
+	#[test]
+	fn non_ascii_characters_are_written_in_code_page_437() {
+		let mut screen = new_screen();
+		input(&mut screen, "é€");
+		assert_eq!([0x82, 0xfe], [screen.buff[0].0[0].0, screen.buff[0].0[1].0]);
+		assert_eq!(Cursor { row: 0, column: 2 }, screen.cursor);
+	}
+
+	#[test]
+	fn scrolling_moves_rows_through_history() {
+		let mut screen = new_screen();
+		write_lines(&mut screen, HEIGHT + 1);
+		assert_eq!("2", text(&screen.buff[0]));
+		assert_eq!(2, screen.history.get_upward_length());
+
+		screen.cursor = Cursor { row: 0, column: 0 };
+		input(&mut screen, "\x18"); // arrow up
+		assert_eq!("1", text(&screen.buff[0]));
+		assert_eq!(1, screen.history.get_upward_length());
+		assert_eq!(1, screen.history.get_downward_length());
+
+		input(&mut screen, "\x1e"); // scroll up
+		assert_eq!("0", text(&screen.buff[0]));
+		assert_eq!(0, screen.history.get_upward_length());
+		assert_eq!(2, screen.history.get_downward_length());
+	}
+
+	#[test]
+	fn history_drops_oldest_rows() {
+		let mut screen = new_screen();
+		write_lines(&mut screen, HEIGHT + 2 * HISTORY_CAPACITY);
+		assert_eq!(HISTORY_CAPACITY, screen.history.get_upward_length());
+		for _ in 0..2 * HISTORY_CAPACITY {
+			screen.shift_downward();
+		}
+		assert_eq!(format!("{}", HISTORY_CAPACITY + 1), text(&screen.buff[0]));
+	}
+
+	#[test]
+	fn insert_shifts_rest_of_line() {
+		let mut screen = new_screen();
+		input(&mut screen, "hello\x1b\x1b\x1bX");
+		assert_eq!("heXllo", text(&screen.buff[0]));
+		assert_eq!(Cursor { row: 0, column: 3 }, screen.cursor);
+	}
+
+	#[test]
+	fn insert_pushes_end_of_line_to_next_row() {
+		let mut screen = new_screen();
+		screen.write_str(&"a".repeat(WIDTH - 1)).unwrap();
+		screen.write_str("bc").unwrap();
+		screen.cursor = Cursor { row: 0, column: 0 };
+		input(&mut screen, "X");
+		assert_eq!(format!("X{}", "a".repeat(WIDTH - 1)), text(&screen.buff[0]));
+		assert_eq!("bc", text(&screen.buff[1]));
+	}
+
+	#[test]
+	fn delete_pulls_next_row_back() {
+		let mut screen = new_screen();
+		screen.write_str(&"a".repeat(WIDTH)).unwrap();
+		screen.write_str("bcd").unwrap();
+		screen.cursor = Cursor { row: 0, column: 10 };
+		input(&mut screen, "\x7f"); // del
+		assert_eq!(format!("{}b", "a".repeat(WIDTH - 1)), text(&screen.buff[0]));
+		assert_eq!("cd", text(&screen.buff[1]));
+	}
+
+	#[test]
+	fn backspace_at_start_of_row_deletes_end_of_row_above() {
+		let mut screen = new_screen();
+		screen.write_str(&"a".repeat(WIDTH)).unwrap();
+		screen.write_str("bc").unwrap();
+		screen.cursor = Cursor { row: 1, column: 0 };
+		input(&mut screen, "\x08");
+		assert_eq!(format!("{}b", "a".repeat(WIDTH - 1)), text(&screen.buff[0]));
+		assert_eq!("c", text(&screen.buff[1]));
+		assert_eq!(Cursor { row: 0, column: WIDTH - 1 }, screen.cursor);
+	}
+
+	#[test]
+	fn delete_pulls_line_back_from_history() {
+		let mut screen = new_screen();
+		write_lines(&mut screen, HEIGHT - 1);
+		screen.write_str(&"a".repeat(WIDTH)).unwrap();
+		screen.write_str("bc").unwrap();
+		// The line spans the last two rows, scroll it down by one row
+		screen.cursor = Cursor { row: 0, column: 0 };
+		input(&mut screen, "\x18");
+		assert_eq!("a".repeat(WIDTH), text(&screen.buff[HEIGHT - 1]));
+		assert_eq!(1, screen.history.get_downward_length());
+
+		screen.cursor = Cursor { row: HEIGHT - 1, column: 0 };
+		input(&mut screen, "\x7f");
+		assert_eq!(format!("{}b", "a".repeat(WIDTH - 1)), text(&screen.buff[HEIGHT - 1]));
+		assert_eq!(1, screen.history.get_downward_length());
+		input(&mut screen, "\x7f");
+		assert_eq!(format!("{}bc", "a".repeat(WIDTH - 2)), text(&screen.buff[HEIGHT - 1]));
+		// The emptied history row is dropped
+		assert_eq!(0, screen.history.get_downward_length());
+	}
+
+	#[test]
+	fn input_slice_holds_characters_and_colors() {
+		let mut screen = new_screen();
+		input(&mut screen, "ls");
+		let slice = screen.get_input_slice().unwrap();
+		assert_eq!(4, slice.len());
+		assert_eq!(b'l', slice[0]);
+		assert_eq!(b's', slice[2]);
+	}
+}
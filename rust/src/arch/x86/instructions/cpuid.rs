@@ -0,0 +1,75 @@
+use bitflags::bitflags;
+use core::arch::x86;
+use crate::arch::x86::registers::rflags::{self, RFlags};
+
+// https://wiki.osdev.org/CPUID
+// https://www.felixcloutier.com/x86/cpuid
+
+pub use core::arch::x86::CpuidResult;
+
+/// Leaf returning the highest standard leaf and the vendor string.
+const LEAF_VENDOR: u32 = 0x0000_0000;
+/// Leaf returning the family/model/stepping and the feature flags.
+const LEAF_FEATURES: u32 = 0x0000_0001;
+/// Leaf returning the highest extended leaf.
+const LEAF_EXTENDED_MAX: u32 = 0x8000_0000;
+/// Leaf returning advanced power management information.
+const LEAF_POWER_MANAGEMENT: u32 = 0x8000_0007;
+
+bitflags! {
+	/// Feature flags in EDX of leaf 1 (only the ones we care about).
+	#[repr(transparent)]
+	#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy)]
+	pub struct Features: u32 {
+		const FPU = 1;
+		/// Time-stamp counter and the RDTSC instruction.
+		const TSC = 1 << 4;
+		/// Model-specific registers and the RDMSR/WRMSR instructions.
+		const MSR = 1 << 5;
+		const APIC = 1 << 9;
+	}
+}
+
+/// Whether the CPU has the CPUID instruction (from some 486 on), i.e. whether
+/// the ID flag of EFLAGS can be toggled.
+pub fn is_supported() -> bool {
+	let flags = rflags::read_raw();
+	unsafe {
+		rflags::write_raw(flags ^ RFlags::ID.bits());
+	}
+	let toggled = rflags::read_raw();
+	unsafe {
+		rflags::write_raw(flags);
+	}
+	0 != (flags ^ toggled) & RFlags::ID.bits()
+}
+
+/// Executes CPUID for `leaf` (and subleaf 0).
+///
+/// Panics if the CPU does not have the instruction, see [`is_supported`].
+pub fn cpuid(leaf: u32) -> CpuidResult {
+	assert!(is_supported(), "cpuid: instruction not supported");
+	unsafe { x86::__cpuid(leaf) }
+}
+
+fn max_leaf() -> u32 {
+	if is_supported() { cpuid(LEAF_VENDOR).eax } else { 0 }
+}
+
+/// Feature flags of the CPU, empty if there is no CPUID instruction.
+pub fn features() -> Features {
+	if LEAF_FEATURES <= max_leaf() {
+		Features::from_bits_truncate(cpuid(LEAF_FEATURES).edx)
+	}
+	else {
+		Features::empty()
+	}
+}
+
+/// Whether the TSC runs at a constant rate in every power state (and is
+/// therefore usable as a clock).
+pub fn has_invariant_tsc() -> bool {
+	is_supported()
+		&& LEAF_POWER_MANAGEMENT <= cpuid(LEAF_EXTENDED_MAX).eax
+		&& 0 != cpuid(LEAF_POWER_MANAGEMENT).edx & (1 << 8)
+}
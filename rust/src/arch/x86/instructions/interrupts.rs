@@ -32,6 +32,20 @@ pub fn disable() {
 	}
 }
 
+/// Atomically enable interrupts and put the CPU to sleep
+///
+/// Executes the `sti; hlt` instruction sequence. Since the `sti` instruction
+/// keeps interrupts disabled until after the immediately following
+/// instruction, no interrupt can come between the two: checking for pending
+/// work with interrupts disabled, then calling this function, cannot miss a
+/// wake-up.
+#[inline]
+pub fn enable_and_hlt() {
+	unsafe {
+		asm!("sti; hlt", options(nomem, nostack));
+	}
+}
+
 /// Run a closure with disabled interrupts.
 ///
 /// Run the given closure, disabling interrupts before running it (if they aren't already disabled).
@@ -1,6 +1,9 @@
 
+pub mod cpuid;
 pub mod interrupts;
 pub mod port;
+pub mod segmentation;
+pub mod tlb;
 
 use core::arch::asm;
 
@@ -10,3 +13,13 @@ pub fn hlt() {
 		asm!("hlt", options(nomem, nostack, preserves_flags));
 	}
 }
+
+/// Reads the time-stamp counter, incremented by the CPU at every clock cycle.
+pub fn rdtsc() -> u64 {
+	let low: u32;
+	let high: u32;
+	unsafe {
+		asm!("rdtsc", out("eax") low, out("edx") high, options(nomem, nostack, preserves_flags));
+	}
+	((high as u64) << 32) | low as u64
+}
@@ -0,0 +1,70 @@
+use core::arch::asm;
+use crate::arch::x86::structures::gdt::SegmentSelector;
+
+// http://css.csail.mit.edu/6.858/2013/readings/i386.pdf -> 5.1.3 Selectors
+
+macro_rules! get_reg_impl {
+	($name:ident, $reg:literal) => {
+		#[doc = concat!("Returns the current value of the ", $reg, " segment register.")]
+		#[inline]
+		pub fn $name() -> u16 {
+			let segment: u16;
+			unsafe {
+				asm!(concat!("mov {0:x}, ", $reg), out(reg) segment, options(nomem, nostack, preserves_flags));
+			}
+			segment
+		}
+	};
+}
+
+get_reg_impl!(cs, "cs");
+get_reg_impl!(ds, "ds");
+get_reg_impl!(es, "es");
+get_reg_impl!(fs, "fs");
+get_reg_impl!(gs, "gs");
+get_reg_impl!(ss, "ss");
+
+macro_rules! set_reg_impl {
+	($name:ident, $reg:literal) => {
+		#[doc = concat!("Loads the ", $reg, " segment register.")]
+		///
+		/// ## Safety
+		///
+		/// The selector must refer to a present data segment of the loaded GDT,
+		/// whose base and limit keep every currently used address valid.
+		#[inline]
+		pub unsafe fn $name(selector: SegmentSelector) -> () {
+			unsafe {
+				asm!(concat!("mov ", $reg, ", {0:x}"), in(reg) selector.0, options(nostack, preserves_flags));
+			}
+		}
+	};
+}
+
+set_reg_impl!(load_ds, "ds");
+set_reg_impl!(load_es, "es");
+set_reg_impl!(load_fs, "fs");
+set_reg_impl!(load_gs, "gs");
+set_reg_impl!(load_ss, "ss");
+
+/// Loads the CS segment register, through a far return to the next instruction.
+///
+/// ## Safety
+///
+/// The selector must refer to a present code segment of the loaded GDT,
+/// whose base and limit keep the running code reachable.
+#[inline]
+pub unsafe fn load_cs(selector: SegmentSelector) -> () {
+	unsafe {
+		asm!(
+			"push {sel}",     // new code segment selector
+			"lea {tmp}, [2f]", // address of label 2 (f: look forward)
+			"push {tmp}",
+			"retf",           // pops EIP then CS, which reloads the descriptor cache
+			"2:",
+			sel = in(reg) selector.0 as u32,
+			tmp = lateout(reg) _,
+			options(preserves_flags)
+		);
+	}
+}
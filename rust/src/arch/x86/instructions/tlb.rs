@@ -0,0 +1,18 @@
+use core::arch::asm;
+use crate::arch::x86::registers::control::Cr3;
+
+/// Invalidates the TLB entry of the page containing the given virtual address.
+///
+/// This is a wrapper around the `invlpg` instruction (i486+).
+#[inline]
+pub fn flush(addr: usize) {
+	unsafe {
+		asm!("invlpg [{}]", in(reg) addr, options(nostack, preserves_flags));
+	}
+}
+
+/// Invalidates every non-global TLB entry by reloading CR3.
+#[inline]
+pub fn flush_all() {
+	unsafe { Cr3::write(Cr3::read()) }
+}
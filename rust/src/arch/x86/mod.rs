@@ -3,3 +3,7 @@ pub mod instructions;
 pub mod registers;
 pub mod structures;
 pub mod pic_8259;
+pub mod pit_8253;
+pub mod ps2_8042;
+pub mod rtc_mc146818;
+pub mod uart_16550;
@@ -7,6 +7,21 @@ const CMD_INIT: u8 = 0x11;
 /// Command sent to acknowledge an interrupt.
 const CMD_END_OF_INTERRUPT: u8 = 0x20;
 
+// https://wiki.osdev.org/8259_PIC#ISR_and_IRR
+
+/// OCW3 making the next read of the command port return the Interrupt Request Register.
+const CMD_READ_IRR: u8 = 0x0A;
+
+/// OCW3 making the next read of the command port return the In-Service Register.
+const CMD_READ_ISR: u8 = 0x0B;
+
+/// Line of the primary PIC the secondary one is wired to.
+const CASCADE_LINE: u8 = 2;
+
+/// Lowest priority line of each PIC, which is reported when a line is
+/// deasserted before the CPU acknowledges the interrupt.
+const SPURIOUS_LINE: u8 = 7;
+
 // The mode in which we want to run our PICs.
 const MODE_8086: u8 = 0x01;
 
@@ -45,6 +60,23 @@ impl Pic {
 	unsafe fn write_mask(&mut self, mask: u8) -> () {
 		self.data.write(mask)
 	}
+
+	/// Reads the lines raised but not yet acknowledged by the CPU.
+	unsafe fn read_irr(&mut self) -> u8 {
+		self.command.write(CMD_READ_IRR);
+		self.command.read()
+	}
+
+	/// Reads the lines acknowledged by the CPU and waiting for an EOI.
+	unsafe fn read_isr(&mut self) -> u8 {
+		self.command.write(CMD_READ_ISR);
+		self.command.read()
+	}
+
+	/// Is the interrupt being serviced, i.e. was it really raised by this PIC?
+	unsafe fn is_in_service(&mut self, interrupt_id: u8) -> bool {
+		0 != self.read_isr() & (1 << (interrupt_id - self.offset))
+	}
 }
 
 /// A pair of chained PICs.  This is the standard setup on x86.
@@ -95,11 +127,6 @@ impl ChainedPics {
 		let mut wait_port: Port<u8> = Port::new(0x80);
 		let mut wait = || wait_port.write(0);
 
-		// Save our original interrupt masks, because I'm too lazy to
-		// figure out reasonable values. We'll restore these when we're
-		// done.
-		let saved_masks = self.read_masks();
-
 		// Tell each PIC that we're going to send it a three-byte
 		// initialization sequence on its data port.
 		self.pics[0].command.write(CMD_INIT);
@@ -125,8 +152,9 @@ impl ChainedPics {
 		self.pics[1].data.write(MODE_8086);
 		wait();
 
-		// Restore our saved masks.
-		self.write_masks(saved_masks[0], saved_masks[1])
+		// Mask every line but the cascade, whatever the firmware left behind:
+		// drivers unmask the lines they handle.
+		self.write_masks(!(1 << CASCADE_LINE), u8::MAX)
 	}
 
 	/// Reads the interrupt masks of both PICs.
@@ -140,6 +168,45 @@ impl ChainedPics {
 		self.pics[1].write_mask(mask2);
 	}
 
+	/// Stops the PICs from raising the interrupt of IRQ `line` (0 to 15).
+	pub unsafe fn mask_irq(&mut self, line: u8) -> () {
+		let pic = &mut self.pics[(line / 8) as usize];
+		let mask = pic.read_mask();
+		pic.write_mask(mask | (1 << (line % 8)));
+	}
+
+	/// Lets the PICs raise the interrupt of IRQ `line` (0 to 15). The cascade
+	/// line is unmasked as well for lines of the secondary PIC.
+	pub unsafe fn unmask_irq(&mut self, line: u8) -> () {
+		let pic = &mut self.pics[(line / 8) as usize];
+		let mask = pic.read_mask();
+		pic.write_mask(mask & !(1 << (line % 8)));
+		if 8 <= line {
+			self.unmask_irq(CASCADE_LINE);
+		}
+	}
+
+	/// Reads the Interrupt Request Registers, IRQ 0 to 15 from the lowest bit.
+	pub unsafe fn read_irr(&mut self) -> u16 {
+		(self.pics[1].read_irr() as u16) << 8 | self.pics[0].read_irr() as u16
+	}
+
+	/// Reads the In-Service Registers, IRQ 0 to 15 from the lowest bit.
+	pub unsafe fn read_isr(&mut self) -> u16 {
+		(self.pics[1].read_isr() as u16) << 8 | self.pics[0].read_isr() as u16
+	}
+
+	/// Is this IRQ7 or IRQ15 interrupt spurious? Such an interrupt is not in
+	/// service, and must not be acknowledged (except for the cascade line of
+	/// the primary PIC, on IRQ15: see [`Self::notify_end_of_interrupt`]).
+	pub unsafe fn is_spurious(&mut self, interrupt_id: u8) -> bool {
+		self.pics.iter_mut().any(|pic| {
+			pic.handles_interrupt(interrupt_id)
+				&& pic.offset + SPURIOUS_LINE == interrupt_id
+				&& !pic.is_in_service(interrupt_id)
+		})
+	}
+
 	/// Disables both PICs by masking all interrupts.
 	pub unsafe fn disable(&mut self) -> () {
 		self.write_masks(u8::MAX, u8::MAX)
@@ -153,12 +220,21 @@ impl ChainedPics {
 	/// Figure out which (if any) PICs in our chain need to know about this
 	/// interrupt.  This is tricky, because all interrupts from `pics[1]`
 	/// get chained through `pics[0]`.
+	///
+	/// A spurious interrupt is not in service, so it is not acknowledged: a
+	/// spurious IRQ7 gets no EOI at all, a spurious IRQ15 only gets one for
+	/// the cascade line of `pics[0]`, which did raise it.
 	pub unsafe fn notify_end_of_interrupt(&mut self, interrupt_id: u8) {
 		if self.handles_interrupt(interrupt_id) {
 			if self.pics[1].handles_interrupt(interrupt_id) {
-				self.pics[1].end_of_interrupt();
+				if !self.is_spurious(interrupt_id) {
+					self.pics[1].end_of_interrupt();
+				}
+				self.pics[0].end_of_interrupt();
+			}
+			else if !self.is_spurious(interrupt_id) {
+				self.pics[0].end_of_interrupt();
 			}
-			self.pics[0].end_of_interrupt();
 		}
 	}
 }
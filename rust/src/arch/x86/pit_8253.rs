@@ -0,0 +1,123 @@
+use super::instructions::port::{Port, PortWriteOnly};
+
+// https://wiki.osdev.org/Programmable_Interval_Timer
+// http://www.osdever.net/bkerndev/Docs/pit.htm
+
+/// Frequency of the oscillator feeding the three channels, in Hz.
+pub const BASE_FREQUENCY: u32 = 1_193_182;
+
+/// Channel 0 data port, its output is wired to IRQ0.
+const CHANNEL_0: u16 = 0x40;
+/// Channel 2 data port, its output is wired to the PC speaker.
+const CHANNEL_2: u16 = 0x42;
+/// Mode/command register.
+const COMMAND: u16 = 0x43;
+/// Keyboard controller port B, which gates channel 2 and reads its output back.
+const PORT_B: u16 = 0x61;
+
+/// Command: channel 0, access mode lobyte/hibyte, mode 2 (rate generator), binary.
+const CHANNEL_0_RATE_GENERATOR: u8 = 0x34;
+/// Command: channel 0, latch the current count.
+const CHANNEL_0_LATCH: u8 = 0x00;
+/// Command: channel 2, access mode lobyte/hibyte, mode 0 (interrupt on terminal count), binary.
+const CHANNEL_2_ONE_SHOT: u8 = 0xB0;
+
+/// Port B: channel 2 counts while its gate is high.
+const PORT_B_CHANNEL_2_GATE: u8 = 1 << 0;
+/// Port B: channel 2 output drives the speaker.
+const PORT_B_SPEAKER: u8 = 1 << 1;
+/// Port B: state of the channel 2 output.
+const PORT_B_CHANNEL_2_OUTPUT: u8 = 1 << 5;
+
+/// Errors reported by [`Pit::set_frequency`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FrequencyError {
+	/// The frequency needs a divisor of 0 or above 65536 (i.e. below about 18.2 Hz).
+	OutOfRange(u32),
+}
+
+/// The 8253/8254 Programmable Interval Timer.
+pub struct Pit {
+	channel_0: Port<u8>,
+	channel_2: Port<u8>,
+	command: PortWriteOnly<u8>,
+	port_b: Port<u8>,
+	/// Current reload value of channel 0, 65536 after a reset.
+	divisor: u32,
+}
+
+impl Pit {
+	/// Create an interface for the PIT.
+	///
+	/// ## Safety
+	///
+	/// The PIT ports must not be used through any other interface.
+	pub const unsafe fn new() -> Self {
+		Self {
+			channel_0: Port::new(CHANNEL_0),
+			channel_2: Port::new(CHANNEL_2),
+			command: PortWriteOnly::new(COMMAND),
+			port_b: Port::new(PORT_B),
+			divisor: 0x10000,
+		}
+	}
+
+	/// Makes channel 0 raise IRQ0 `frequency` times per second.
+	///
+	/// The divisor is rounded to the nearest integer, so the frequency actually
+	/// programmed (see [`Self::divisor`]) may slightly differ.
+	pub fn set_frequency(&mut self, frequency: u32) -> Result<(), FrequencyError> {
+		if 0 == frequency {
+			return Err(FrequencyError::OutOfRange(frequency));
+		}
+		let divisor = (BASE_FREQUENCY + frequency / 2) / frequency;
+		if 0 == divisor || 0x10000 < divisor {
+			return Err(FrequencyError::OutOfRange(frequency));
+		}
+		unsafe {
+			self.command.write(CHANNEL_0_RATE_GENERATOR);
+			// A reload value of 0 stands for 65536
+			self.channel_0.write(divisor as u8);
+			self.channel_0.write((divisor >> 8) as u8);
+		}
+		self.divisor = divisor;
+		Ok(())
+	}
+
+	/// Oscillator periods between two IRQ0.
+	pub fn divisor(&self) -> u32 {
+		self.divisor
+	}
+
+	/// Reads the current count of channel 0, decremented at [`BASE_FREQUENCY`]
+	/// from [`Self::divisor`] down to 1.
+	pub fn read_count(&mut self) -> u16 {
+		unsafe {
+			self.command.write(CHANNEL_0_LATCH);
+			let low = self.channel_0.read();
+			let high = self.channel_0.read();
+			(high as u16) << 8 | low as u16
+		}
+	}
+
+	/// Starts channel 2 counting down from `count`, with the speaker off:
+	/// [`Self::channel_2_expired`] becomes true `count` oscillator periods later.
+	///
+	/// Channel 2 has no IRQ, which makes it handy to measure time while
+	/// channel 0 keeps running.
+	pub fn start_channel_2(&mut self, count: u16) -> () {
+		unsafe {
+			let port_b = self.port_b.read() & !(PORT_B_CHANNEL_2_GATE | PORT_B_SPEAKER);
+			self.port_b.write(port_b);
+			self.command.write(CHANNEL_2_ONE_SHOT);
+			self.channel_2.write(count as u8);
+			self.channel_2.write((count >> 8) as u8);
+			// Counting starts on the rising edge of the gate
+			self.port_b.write(port_b | PORT_B_CHANNEL_2_GATE);
+		}
+	}
+
+	pub fn channel_2_expired(&mut self) -> bool {
+		0 != unsafe { self.port_b.read() } & PORT_B_CHANNEL_2_OUTPUT
+	}
+}
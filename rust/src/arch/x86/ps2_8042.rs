@@ -0,0 +1,324 @@
+use bitflags::bitflags;
+use super::instructions::port::{Port, PortReadOnly, PortWriteOnly};
+
+// https://wiki.osdev.org/I8042_PS/2_Controller
+// https://wiki.osdev.org/PS/2_Keyboard
+
+/// Data port: bytes from the devices and the controller, bytes to port 1.
+const DATA: u16 = 0x60;
+/// Status register (read).
+const STATUS: u16 = 0x64;
+/// Command register (write).
+const COMMAND: u16 = 0x64;
+
+/// Polls of the status register before giving up, about a second on real
+/// hardware (the keyboard self-test alone may take 500 ms).
+const TIMEOUT_POLLS: u32 = 1_000_000;
+/// Times a device command is sent again when the device asks for it.
+const RESEND_TRIES: u32 = 3;
+
+bitflags! {
+	/// Status register.
+	#[repr(transparent)]
+	#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy)]
+	pub struct Status: u8 {
+		/// A byte is waiting on the data port.
+		const OUTPUT_FULL = 1;
+		/// The controller has not taken the last byte written yet.
+		const INPUT_FULL = 1 << 1;
+		/// Set by the firmware once the POST passed.
+		const SYSTEM = 1 << 2;
+		/// The last byte written was a command, not data.
+		const COMMAND = 1 << 3;
+		const TIMEOUT_ERROR = 1 << 6;
+		const PARITY_ERROR = 1 << 7;
+	}
+}
+
+bitflags! {
+	/// Controller configuration byte.
+	#[repr(transparent)]
+	#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy)]
+	pub struct Config: u8 {
+		/// Port 1 raises IRQ1.
+		const PORT_1_INTERRUPT = 1;
+		/// Port 2 raises IRQ12.
+		const PORT_2_INTERRUPT = 1 << 1;
+		const SYSTEM = 1 << 2;
+		const PORT_1_CLOCK_DISABLED = 1 << 4;
+		const PORT_2_CLOCK_DISABLED = 1 << 5;
+		/// Scancodes of port 1 are translated from set 2 to set 1.
+		const PORT_1_TRANSLATION = 1 << 6;
+	}
+}
+
+bitflags! {
+	/// Keyboard LEDs, as sent with the set LEDs command.
+	#[repr(transparent)]
+	#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy)]
+	pub struct Leds: u8 {
+		const SCROLL_LOCK = 1;
+		const NUM_LOCK = 1 << 1;
+		const CAPS_LOCK = 1 << 2;
+	}
+}
+
+/// Controller command: read the configuration byte.
+const CMD_READ_CONFIG: u8 = 0x20;
+/// Controller command: write the configuration byte.
+const CMD_WRITE_CONFIG: u8 = 0x60;
+const CMD_DISABLE_PORT_2: u8 = 0xA7;
+const CMD_ENABLE_PORT_2: u8 = 0xA8;
+const CMD_TEST_PORT_2: u8 = 0xA9;
+const CMD_TEST_CONTROLLER: u8 = 0xAA;
+const CMD_TEST_PORT_1: u8 = 0xAB;
+const CMD_DISABLE_PORT_1: u8 = 0xAD;
+const CMD_ENABLE_PORT_1: u8 = 0xAE;
+/// Controller command: pulse the CPU reset line.
+const CMD_RESET_SYSTEM: u8 = 0xFE;
+
+/// Answer to [`CMD_TEST_CONTROLLER`] when it passed.
+const CONTROLLER_TEST_PASSED: u8 = 0x55;
+/// Answer to [`CMD_TEST_PORT_1`] and [`CMD_TEST_PORT_2`] when it passed.
+const PORT_TEST_PASSED: u8 = 0x00;
+
+/// Keyboard command: set the LEDs, followed by a [`Leds`] byte.
+const KBD_SET_LEDS: u8 = 0xED;
+/// Keyboard command: select the scancode set, followed by its number.
+const KBD_SCANCODE_SET: u8 = 0xF0;
+/// Keyboard command: set the repeat rate and delay, followed by their codes.
+const KBD_SET_TYPEMATIC: u8 = 0xF3;
+const KBD_ENABLE_SCANNING: u8 = 0xF4;
+/// Keyboard command: reset and run the self-test (BAT).
+const KBD_RESET: u8 = 0xFF;
+
+const KBD_ACK: u8 = 0xFA;
+const KBD_RESEND: u8 = 0xFE;
+/// Sent by the keyboard once its self-test passed.
+const KBD_SELF_TEST_PASSED: u8 = 0xAA;
+
+/// Typematic period unit, in microseconds.
+const TYPEMATIC_UNIT_US: u32 = 4_170;
+/// Typematic delay unit, in milliseconds.
+const TYPEMATIC_DELAY_UNIT_MS: u32 = 250;
+
+/// Errors reported by [`Controller`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+	/// The controller or the keyboard did not answer in time.
+	Timeout,
+	/// The controller self-test answered this instead of 0x55.
+	ControllerTestFailed(u8),
+	/// The port 1 interface test answered this error code.
+	PortTestFailed(u8),
+	/// The keyboard self-test answered this instead of 0xAA.
+	KeyboardTestFailed(u8),
+	/// The keyboard answered this to a command instead of an ACK.
+	NotAcknowledged(u8),
+	/// The repeat rate is not between 2 and 30 characters per second, or
+	/// the delay not between 250 and 1000 ms.
+	OutOfRange,
+}
+
+/// The 8042 PS/2 controller, with a keyboard on its first port.
+pub struct Controller {
+	data: Port<u8>,
+	status: PortReadOnly<u8>,
+	command: PortWriteOnly<u8>,
+}
+
+impl Controller {
+	/// Create an interface for the PS/2 controller.
+	///
+	/// ## Safety
+	///
+	/// The controller ports must not be used through any other interface.
+	pub const unsafe fn new() -> Self {
+		Self {
+			data: Port::new(DATA),
+			status: PortReadOnly::new(STATUS),
+			command: PortWriteOnly::new(COMMAND),
+		}
+	}
+
+	/// Brings the controller and the keyboard to a known state, whatever the
+	/// firmware left: both ports disabled and flushed, self-tests, keyboard
+	/// reset to scancode set 2 (translated to set 1 by the controller) with its
+	/// LEDs off. On success, port 1 is enabled and raises IRQ1; port 2 is left
+	/// disabled. Returns whether the controller has a second port.
+	pub fn init(&mut self) -> Result<bool, Error> {
+		self.write_command(CMD_DISABLE_PORT_1)?;
+		self.write_command(CMD_DISABLE_PORT_2)?;
+		self.flush();
+
+		// No interrupts nor translation while talking to the keyboard
+		let mut config = self.read_config()?;
+		config.remove(Config::PORT_1_INTERRUPT | Config::PORT_2_INTERRUPT | Config::PORT_1_TRANSLATION);
+		self.write_config(config)?;
+
+		self.write_command(CMD_TEST_CONTROLLER)?;
+		match self.read_data()? {
+			CONTROLLER_TEST_PASSED => {},
+			answer => return Err(Error::ControllerTestFailed(answer)),
+		}
+		// The self-test may reset the controller
+		self.write_config(config)?;
+		// A disabled port 2 clock only hints at a second port: it exists if
+		// enabling it clears the bit
+		let dual_channel = config.contains(Config::PORT_2_CLOCK_DISABLED) && {
+			self.write_command(CMD_ENABLE_PORT_2)?;
+			let enabled = !self.read_config()?.contains(Config::PORT_2_CLOCK_DISABLED);
+			self.write_command(CMD_DISABLE_PORT_2)?;
+			enabled
+		};
+		self.write_command(CMD_TEST_PORT_1)?;
+		match self.read_data()? {
+			PORT_TEST_PASSED => {},
+			answer => return Err(Error::PortTestFailed(answer)),
+		}
+		if dual_channel {
+			self.write_command(CMD_TEST_PORT_2)?;
+			if PORT_TEST_PASSED != self.read_data()? {
+				crate::warn!("ps2: port 2 interface test failed");
+			}
+		}
+
+		self.write_command(CMD_ENABLE_PORT_1)?;
+		self.send_to_keyboard(KBD_RESET)?;
+		match self.read_data()? {
+			KBD_SELF_TEST_PASSED => {},
+			answer => return Err(Error::KeyboardTestFailed(answer)),
+		}
+		self.send_to_keyboard(KBD_SCANCODE_SET)?;
+		self.send_to_keyboard(2)?;
+		self.set_leds(Leds::empty())?;
+		self.send_to_keyboard(KBD_ENABLE_SCANNING)?;
+
+		config.remove(Config::PORT_1_CLOCK_DISABLED);
+		config.insert(Config::PORT_1_INTERRUPT | Config::PORT_1_TRANSLATION);
+		self.write_config(config)?;
+		Ok(dual_channel)
+	}
+
+	pub fn status(&mut self) -> Status {
+		Status::from_bits_truncate(unsafe { self.status.read() })
+	}
+
+	/// Returns the byte waiting on the data port, if any.
+	pub fn receive(&mut self) -> Option<u8> {
+		if self.status().contains(Status::OUTPUT_FULL) {
+			Some(unsafe { self.data.read() })
+		}
+		else {
+			None
+		}
+	}
+
+	/// Must not race with the IRQ1 handler, which would take the answer.
+	pub fn set_leds(&mut self, leds: Leds) -> Result<(), Error> {
+		self.send_to_keyboard(KBD_SET_LEDS)?;
+		self.send_to_keyboard(leds.bits())
+	}
+
+	/// Sets the repeat rate (2 to 30 characters per second, rounded to the
+	/// nearest the keyboard supports) and the delay before the repeat starts
+	/// (250 to 1000 ms, by steps of 250 ms). Must not race with the IRQ1 handler.
+	pub fn set_typematic(&mut self, rate: u32, delay: u32) -> Result<(), Error> {
+		let byte = typematic_byte(rate, delay).ok_or(Error::OutOfRange)?;
+		self.send_to_keyboard(KBD_SET_TYPEMATIC)?;
+		self.send_to_keyboard(byte)
+	}
+
+	/// Pulses the CPU reset line.
+	pub fn reset_system(&mut self) -> () {
+		let _result: Result<(), Error> = self.write_command(CMD_RESET_SYSTEM);
+	}
+
+	fn wait_for(&mut self, status: Status, set: bool) -> Result<(), Error> {
+		for _ in 0..TIMEOUT_POLLS {
+			if set == self.status().contains(status) {
+				return Ok(());
+			}
+			core::hint::spin_loop();
+		}
+		Err(Error::Timeout)
+	}
+
+	fn read_data(&mut self) -> Result<u8, Error> {
+		self.wait_for(Status::OUTPUT_FULL, true)?;
+		Ok(unsafe { self.data.read() })
+	}
+
+	fn write_data(&mut self, byte: u8) -> Result<(), Error> {
+		self.wait_for(Status::INPUT_FULL, false)?;
+		unsafe { self.data.write(byte) };
+		Ok(())
+	}
+
+	fn write_command(&mut self, command: u8) -> Result<(), Error> {
+		self.wait_for(Status::INPUT_FULL, false)?;
+		unsafe { self.command.write(command) };
+		Ok(())
+	}
+
+	/// Drops the bytes left in the output buffer.
+	fn flush(&mut self) -> () {
+		for _ in 0..TIMEOUT_POLLS {
+			if self.receive().is_none() {
+				break;
+			}
+		}
+	}
+
+	fn read_config(&mut self) -> Result<Config, Error> {
+		self.write_command(CMD_READ_CONFIG)?;
+		Ok(Config::from_bits_retain(self.read_data()?))
+	}
+
+	fn write_config(&mut self, config: Config) -> Result<(), Error> {
+		self.write_command(CMD_WRITE_CONFIG)?;
+		self.write_data(config.bits())
+	}
+
+	/// Sends a byte to the keyboard and waits for its ACK.
+	fn send_to_keyboard(&mut self, byte: u8) -> Result<(), Error> {
+		let mut answer = KBD_RESEND;
+		for _ in 0..RESEND_TRIES {
+			self.write_data(byte)?;
+			answer = self.read_data()?;
+			if KBD_RESEND != answer {
+				break;
+			}
+		}
+		if KBD_ACK == answer { Ok(()) } else { Err(Error::NotAcknowledged(answer)) }
+	}
+}
+
+/// Encodes the set typematic argument: bits 0-4 select the repeat period,
+/// (8 + bits 0-2) * 2^(bits 3-4) * 4.17 ms, bits 5-6 the delay.
+fn typematic_byte(rate: u32, delay: u32) -> Option<u8> {
+	if !(2..=30).contains(&rate) || !(250..=1000).contains(&delay) {
+		return None;
+	}
+	let period = 1_000_000 / rate;
+	let rate_code = (0..32u32)
+		.min_by_key(|code| ((8 + (code & 7)) * (1 << (code >> 3)) * TYPEMATIC_UNIT_US).abs_diff(period))
+		.unwrap();
+	let delay_code = (delay + TYPEMATIC_DELAY_UNIT_MS / 2) / TYPEMATIC_DELAY_UNIT_MS - 1;
+	Some((delay_code << 5 | rate_code) as u8)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test_case]
+	fn typematic_byte_encodes_rate_and_delay() {
+		// Fastest, slowest, and the power-on default (10.9 cps, 500 ms)
+		assert_eq!(Some(0x00), typematic_byte(30, 250));
+		assert_eq!(Some(0x7F), typematic_byte(2, 1000));
+		assert_eq!(Some(0x2B), typematic_byte(11, 500));
+		assert_eq!(None, typematic_byte(31, 500));
+		assert_eq!(None, typematic_byte(10, 100));
+	}
+}
@@ -0,0 +1,216 @@
+use core::arch::asm;
+use bitflags::bitflags;
+
+// http://css.csail.mit.edu/6.858/2013/readings/i386.pdf -> 4.1.3 Control Registers
+// https://wiki.osdev.org/CPU_Registers_x86#Control_Registers
+
+bitflags! {
+	/// Configuration flags of the CR0 register.
+	#[repr(transparent)]
+	#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy)]
+	pub struct Cr0Flags: u32 {
+		/// Enables protected mode.
+		const PROTECTION_ENABLE = 1;
+		/// Controls the interaction of `wait` with the `TASK_SWITCHED` flag.
+		const MONITOR_COPROCESSOR = 1 << 1;
+		/// Forces all x87 instructions to cause a #NM exception.
+		const EMULATE_COPROCESSOR = 1 << 2;
+		/// Set by hardware on every task switch.
+		const TASK_SWITCHED = 1 << 3;
+		/// Indicates support of 387DX math coprocessor instructions.
+		const EXTENSION_TYPE = 1 << 4;
+		/// Enables the native (internal) x87 error reporting mechanism.
+		const NUMERIC_ERROR = 1 << 5;
+		/// Prevents ring 0 from writing to read-only pages.
+		const WRITE_PROTECT = 1 << 16;
+		/// Enables automatic alignment checking (together with `RFlags::ALIGNMENT_CHECK`).
+		const ALIGNMENT_MASK = 1 << 18;
+		/// Ignored on modern processors.
+		const NOT_WRITE_THROUGH = 1 << 29;
+		/// Disables the memory caches.
+		const CACHE_DISABLE = 1 << 30;
+		/// Enables paging (requires `PROTECTION_ENABLE`).
+		const PAGING = 1 << 31;
+	}
+}
+
+bitflags! {
+	/// Configuration flags of the CR4 register.
+	#[repr(transparent)]
+	#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy)]
+	pub struct Cr4Flags: u32 {
+		/// Enables virtual-8086 mode extensions.
+		const VIRTUAL_8086_MODE_EXTENSIONS = 1;
+		/// Enables protected-mode virtual interrupts.
+		const PROTECTED_MODE_VIRTUAL_INTERRUPTS = 1 << 1;
+		/// Restricts `rdtsc` to ring 0.
+		const TIMESTAMP_DISABLE = 1 << 2;
+		/// Enables I/O breakpoints.
+		const DEBUGGING_EXTENSIONS = 1 << 3;
+		/// Enables 4 MiB pages in 32-bit paging.
+		const PAGE_SIZE_EXTENSION = 1 << 4;
+		/// Enables physical address extension (36-bit addresses, 3-level paging).
+		const PHYSICAL_ADDRESS_EXTENSION = 1 << 5;
+		/// Enables the machine check exception.
+		const MACHINE_CHECK_EXCEPTION = 1 << 6;
+		/// Enables global pages (not flushed on CR3 writes).
+		const PAGE_GLOBAL = 1 << 7;
+	}
+}
+
+/// Various control flags modifying the basic operation of the CPU.
+#[derive(Debug)]
+pub struct Cr0;
+
+/// Linear address that caused the last page fault.
+#[derive(Debug)]
+pub struct Cr2;
+
+/// Physical address of the page directory and its caching flags.
+#[derive(Debug)]
+pub struct Cr3;
+
+/// Various control flags modifying the basic operation of the CPU while in protected mode.
+#[derive(Debug)]
+pub struct Cr4;
+
+impl Cr0 {
+	/// Reads the current CR0 flags, dropping any unknown bits.
+	#[inline]
+	pub fn read() -> Cr0Flags {
+		Cr0Flags::from_bits_truncate(Self::read_raw())
+	}
+
+	#[inline]
+	pub fn read_raw() -> u32 {
+		let value: u32;
+		unsafe {
+			asm!("mov {}, cr0", out(reg) value, options(nomem, nostack, preserves_flags));
+		}
+		value
+	}
+
+	/// Writes CR0 flags, preserving reserved bits.
+	///
+	/// ## Safety
+	///
+	/// Unsafe because it is possible to violate memory safety by e.g. disabling paging.
+	#[inline]
+	pub unsafe fn write(flags: Cr0Flags) {
+		let reserved = Self::read_raw() & !(Cr0Flags::all().bits());
+		unsafe {
+			Self::write_raw(reserved | flags.bits());
+		}
+	}
+
+	/// ## Safety
+	///
+	/// See [`Cr0::write`].
+	#[inline]
+	pub unsafe fn write_raw(value: u32) {
+		unsafe {
+			asm!("mov cr0, {}", in(reg) value, options(nostack, preserves_flags));
+		}
+	}
+
+	/// Updates CR0 flags through the given closure.
+	///
+	/// ## Safety
+	///
+	/// See [`Cr0::write`].
+	#[inline]
+	pub unsafe fn update<F>(f: F)
+	where
+		F: FnOnce(&mut Cr0Flags),
+	{
+		let mut flags = Self::read();
+		f(&mut flags);
+		unsafe {
+			Self::write(flags);
+		}
+	}
+}
+
+impl Cr2 {
+	/// Reads the faulting linear address, only meaningful inside a page fault handler.
+	#[inline]
+	pub fn read() -> u32 {
+		let value: u32;
+		unsafe {
+			asm!("mov {}, cr2", out(reg) value, options(nomem, nostack, preserves_flags));
+		}
+		value
+	}
+}
+
+impl Cr3 {
+	/// Reads the physical address of the current page directory.
+	#[inline]
+	pub fn read() -> u32 {
+		let value: u32;
+		unsafe {
+			asm!("mov {}, cr3", out(reg) value, options(nomem, nostack, preserves_flags));
+		}
+		value & !0xfff
+	}
+
+	/// Loads a new page directory, which flushes every non-global TLB entry.
+	///
+	/// ## Safety
+	///
+	/// `page_directory` must be the 4 KiB aligned physical address of a valid page
+	/// directory that maps the currently running code.
+	#[inline]
+	pub unsafe fn write(page_directory: u32) {
+		unsafe {
+			asm!("mov cr3, {}", in(reg) page_directory, options(nostack, preserves_flags));
+		}
+	}
+}
+
+impl Cr4 {
+	/// Reads the current CR4 flags, dropping any unknown bits.
+	#[inline]
+	pub fn read() -> Cr4Flags {
+		Cr4Flags::from_bits_truncate(Self::read_raw())
+	}
+
+	#[inline]
+	pub fn read_raw() -> u32 {
+		let value: u32;
+		unsafe {
+			asm!("mov {}, cr4", out(reg) value, options(nomem, nostack, preserves_flags));
+		}
+		value
+	}
+
+	/// Writes CR4 flags, preserving reserved bits.
+	///
+	/// ## Safety
+	///
+	/// Unsafe because it is possible to violate memory safety by e.g. changing the paging mode.
+	#[inline]
+	pub unsafe fn write(flags: Cr4Flags) {
+		let reserved = Self::read_raw() & !(Cr4Flags::all().bits());
+		unsafe {
+			asm!("mov cr4, {}", in(reg) reserved | flags.bits(), options(nostack, preserves_flags));
+		}
+	}
+
+	/// Updates CR4 flags through the given closure.
+	///
+	/// ## Safety
+	///
+	/// See [`Cr4::write`].
+	#[inline]
+	pub unsafe fn update<F>(f: F)
+	where
+		F: FnOnce(&mut Cr4Flags),
+	{
+		let mut flags = Self::read();
+		f(&mut flags);
+		unsafe {
+			Self::write(flags);
+		}
+	}
+}
@@ -1,4 +1,5 @@
 
+pub mod control;
 pub mod rflags;
 
 pub fn get_stack_frame() -> (u32, u32) {
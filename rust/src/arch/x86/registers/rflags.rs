@@ -9,6 +9,8 @@ bitflags! {
 	#[repr(transparent)]
 	#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy)]
 	pub struct RFlags: u32 {
+		/// Can be toggled if the CPUID instruction is supported.
+		const ID = 1 << 21;
 		/// Enable the virtual-8086 mode.
 		const VIRTUAL_8086_MODE = 1 << 17;
 		/// Allows to restart an instruction following an instruction breakpoint.
@@ -0,0 +1,166 @@
+use super::instructions::port::{Port, PortWriteOnly};
+
+// https://wiki.osdev.org/CMOS
+// https://wiki.osdev.org/RTC
+
+/// Selects the CMOS register accessed through [`DATA`].
+const ADDRESS: u16 = 0x70;
+const DATA: u16 = 0x71;
+
+/// Set in the address byte to keep NMIs disabled while a register is selected.
+const NMI_DISABLE: u8 = 0x80;
+
+const REGISTER_SECONDS: u8 = 0x00;
+const REGISTER_MINUTES: u8 = 0x02;
+const REGISTER_HOURS: u8 = 0x04;
+const REGISTER_DAY: u8 = 0x07;
+const REGISTER_MONTH: u8 = 0x08;
+const REGISTER_YEAR: u8 = 0x09;
+const REGISTER_STATUS_A: u8 = 0x0A;
+const REGISTER_STATUS_B: u8 = 0x0B;
+
+/// Status A: the RTC is updating its registers, which may be inconsistent.
+const STATUS_A_UPDATE_IN_PROGRESS: u8 = 1 << 7;
+/// Status B: the hours count from 0 to 23 (instead of 1 to 12, with [`HOURS_PM`]).
+const STATUS_B_24_HOUR: u8 = 1 << 1;
+/// Status B: the registers hold binary values (instead of BCD).
+const STATUS_B_BINARY: u8 = 1 << 2;
+/// Hours register in 12 hour mode: afternoon.
+const HOURS_PM: u8 = 1 << 7;
+
+/// Date and time as read from the RTC, already decoded (binary, 24 hour).
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RtcTime {
+	pub second: u8,
+	pub minute: u8,
+	pub hour: u8,
+	pub day: u8,
+	pub month: u8,
+	/// Year of the century, from 0 to 99.
+	pub year: u8,
+}
+
+/// The MC146818 real-time clock of the CMOS.
+pub struct Rtc {
+	address: PortWriteOnly<u8>,
+	data: Port<u8>,
+}
+
+impl Rtc {
+	/// Create an interface for the RTC.
+	///
+	/// ## Safety
+	///
+	/// The CMOS ports must not be used through any other interface.
+	pub const unsafe fn new() -> Self {
+		Self {
+			address: PortWriteOnly::new(ADDRESS),
+			data: Port::new(DATA),
+		}
+	}
+
+	/// NMIs are disabled during the access only: the address port also holds
+	/// the NMI mask of the whole machine.
+	fn read_register(&mut self, register: u8) -> u8 {
+		unsafe {
+			self.address.write(NMI_DISABLE | register);
+			let value = self.data.read();
+			self.address.write(register);
+			value
+		}
+	}
+
+	pub fn update_in_progress(&mut self) -> bool {
+		0 != self.read_register(REGISTER_STATUS_A) & STATUS_A_UPDATE_IN_PROGRESS
+	}
+
+	fn read_raw(&mut self) -> RtcTime {
+		while self.update_in_progress() {
+			core::hint::spin_loop();
+		}
+		RtcTime {
+			second: self.read_register(REGISTER_SECONDS),
+			minute: self.read_register(REGISTER_MINUTES),
+			hour: self.read_register(REGISTER_HOURS),
+			day: self.read_register(REGISTER_DAY),
+			month: self.read_register(REGISTER_MONTH),
+			year: self.read_register(REGISTER_YEAR),
+		}
+	}
+
+	/// Reads the date and time.
+	///
+	/// An update may still start between the check of the update flag and the
+	/// last read, so the registers are read until two reads in a row agree.
+	pub fn read(&mut self) -> RtcTime {
+		let mut time = self.read_raw();
+		loop {
+			let again = self.read_raw();
+			if again == time {
+				break;
+			}
+			time = again;
+		}
+		let status_b = self.read_register(REGISTER_STATUS_B);
+		decode(time, status_b)
+	}
+}
+
+fn bcd_to_binary(value: u8) -> u8 {
+	(value >> 4) * 10 + (value & 0x0F)
+}
+
+/// Converts raw register values to binary, 24 hour values according to the
+/// format described by status register B.
+fn decode(raw: RtcTime, status_b: u8) -> RtcTime {
+	let pm = 0 != raw.hour & HOURS_PM;
+	let mut time = RtcTime {
+		hour: raw.hour & !HOURS_PM,
+		..raw
+	};
+	if 0 == status_b & STATUS_B_BINARY {
+		time = RtcTime {
+			second: bcd_to_binary(time.second),
+			minute: bcd_to_binary(time.minute),
+			hour: bcd_to_binary(time.hour),
+			day: bcd_to_binary(time.day),
+			month: bcd_to_binary(time.month),
+			year: bcd_to_binary(time.year),
+		};
+	}
+	// 12 AM is midnight, 12 PM is noon
+	if 0 == status_b & STATUS_B_24_HOUR {
+		time.hour %= 12;
+		if pm {
+			time.hour += 12;
+		}
+	}
+	time
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const RAW: RtcTime = RtcTime { second: 0x59, minute: 0x30, hour: 0x12, day: 0x18, month: 0x10, year: 0x26 };
+
+	#[test_case]
+	fn decode_bcd_24_hour() {
+		let time = decode(RAW, STATUS_B_24_HOUR);
+		assert_eq!(RtcTime { second: 59, minute: 30, hour: 12, day: 18, month: 10, year: 26 }, time);
+	}
+
+	#[test_case]
+	fn decode_bcd_12_hour() {
+		assert_eq!(0, decode(RAW, 0).hour); // 12 AM
+		assert_eq!(12, decode(RtcTime { hour: HOURS_PM | 0x12, ..RAW }, 0).hour); // 12 PM
+		assert_eq!(23, decode(RtcTime { hour: HOURS_PM | 0x11, ..RAW }, 0).hour); // 11 PM
+	}
+
+	#[test_case]
+	fn decode_binary_12_hour() {
+		let time = decode(RtcTime { hour: HOURS_PM | 1, minute: 59, ..RAW }, STATUS_B_BINARY);
+		assert_eq!(13, time.hour);
+		assert_eq!(59, time.minute);
+	}
+}
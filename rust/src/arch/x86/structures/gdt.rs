@@ -0,0 +1,341 @@
+use core::arch::asm;
+use core::fmt::{self, Debug};
+use bitflags::bitflags;
+use super::idt::DescriptorTablePointer;
+use super::tss::TaskStateSegment;
+
+// http://css.csail.mit.edu/6.858/2013/readings/i386.pdf -> 5.1 Segment Translation
+// http://css.csail.mit.edu/6.858/2013/readings/i386.pdf -> 6.3.1 Descriptors Store Protection Parameters
+// https://wiki.osdev.org/Global_Descriptor_Table
+
+/// Load a GDT.
+///
+/// ## Safety
+///
+/// This function is unsafe because the caller must ensure that the given
+/// `DescriptorTablePointer` points to a valid GDT, which stays in place as
+/// long as it is loaded.
+#[inline]
+pub unsafe fn lgdt(gdt: &DescriptorTablePointer) {
+	unsafe {
+		asm!("lgdt [{}]", in(reg) gdt, options(readonly, nostack, preserves_flags));
+	}
+}
+
+/// Get the address of the current GDT.
+#[inline]
+pub fn sgdt() -> DescriptorTablePointer {
+	let mut gdt: DescriptorTablePointer = DescriptorTablePointer {
+		limit: 0,
+		base: 0,
+	};
+	unsafe {
+		asm!("sgdt [{}]", in(reg) &mut gdt, options(nostack, preserves_flags));
+	}
+	gdt
+}
+
+// ===== SegmentSelector =====
+
+/// The four protection rings, 0 being the most privileged one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum PrivilegeLevel {
+	Ring0 = 0,
+	Ring1 = 1,
+	Ring2 = 2,
+	Ring3 = 3,
+}
+
+impl PrivilegeLevel {
+	/// Creates a privilege level from its two lowest bits.
+	pub const fn from_u16(value: u16) -> Self {
+		match value & 0b11 {
+			0 => Self::Ring0,
+			1 => Self::Ring1,
+			2 => Self::Ring2,
+			_ => Self::Ring3,
+		}
+	}
+}
+
+/// Index of a descriptor in the GDT, along with the requested privilege level.
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct SegmentSelector(pub u16);
+
+impl SegmentSelector {
+	/// Creates a selector for the GDT entry at `index`.
+	pub const fn new(index: u16, rpl: PrivilegeLevel) -> Self {
+		Self(index << 3 | rpl as u16)
+	}
+
+	/// Index of the selected descriptor.
+	pub const fn index(self) -> u16 {
+		self.0 >> 3
+	}
+
+	/// Requested privilege level.
+	pub const fn rpl(self) -> PrivilegeLevel {
+		PrivilegeLevel::from_u16(self.0)
+	}
+}
+
+impl Debug for SegmentSelector {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.debug_struct("SegmentSelector")
+			.field("index", &self.index())
+			.field("rpl", &self.rpl())
+			.finish()
+	}
+}
+
+// ===== Descriptor =====
+
+bitflags! {
+	/// Access byte and flags of a segment descriptor, at their position in the descriptor.
+	#[repr(transparent)]
+	#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy)]
+	pub struct DescriptorFlags: u64 {
+		/// Set by the CPU when the segment is loaded.
+		const ACCESSED = 1 << 40;
+		/// Data segments: writable. Code segments: readable.
+		const WRITABLE = 1 << 41;
+		/// Data segments: expands down. Code segments: callable from less privileged rings.
+		const CONFORMING = 1 << 42;
+		/// Code segment if set, data segment otherwise.
+		const EXECUTABLE = 1 << 43;
+		/// Code or data segment if set, system segment (TSS, LDT, gates) otherwise.
+		const USER_SEGMENT = 1 << 44;
+		/// Descriptor privilege level 3 (both bits set).
+		const DPL_RING_3 = 3 << 45;
+		const PRESENT = 1 << 47;
+		/// Bits 0..16 of the limit.
+		const LIMIT_0_15 = 0xffff;
+		/// Bits 16..20 of the limit.
+		const LIMIT_16_19 = 0xf << 48;
+		/// Available for use by the operating system.
+		const AVAILABLE = 1 << 52;
+		/// 32-bit segment if set, 16-bit otherwise.
+		const DEFAULT_SIZE = 1 << 54;
+		/// The limit is counted in 4 KiB units if set, in bytes otherwise.
+		const GRANULARITY = 1 << 55;
+
+		/// Bits shared by the flat 4 GiB segments.
+		const COMMON = Self::USER_SEGMENT.bits()
+			| Self::PRESENT.bits()
+			| Self::WRITABLE.bits()
+			| Self::LIMIT_0_15.bits()
+			| Self::LIMIT_16_19.bits()
+			| Self::DEFAULT_SIZE.bits()
+			| Self::GRANULARITY.bits();
+		/// A flat ring 0 code segment (access byte 0x9a).
+		const KERNEL_CODE = Self::COMMON.bits() | Self::EXECUTABLE.bits();
+		/// A flat ring 0 data segment (access byte 0x92).
+		const KERNEL_DATA = Self::COMMON.bits();
+		/// A flat ring 3 code segment (access byte 0xfa).
+		const USER_CODE = Self::KERNEL_CODE.bits() | Self::DPL_RING_3.bits();
+		/// A flat ring 3 data segment (access byte 0xf2).
+		const USER_DATA = Self::KERNEL_DATA.bits() | Self::DPL_RING_3.bits();
+	}
+}
+
+/// System segment type of an available 32-bit TSS.
+const TSS_AVAILABLE: u64 = 0x9;
+/// System segment type of a busy 32-bit TSS, set by `ltr` and task switches.
+const TSS_BUSY: u64 = 0xb;
+
+/// A segment descriptor, as stored in the GDT.
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct Descriptor(u64);
+
+impl Descriptor {
+	/// The first GDT entry, never used by the CPU.
+	pub const fn null() -> Self {
+		Self(0)
+	}
+
+	pub const fn kernel_code_segment() -> Self {
+		Self(DescriptorFlags::KERNEL_CODE.bits())
+	}
+
+	pub const fn kernel_data_segment() -> Self {
+		Self(DescriptorFlags::KERNEL_DATA.bits())
+	}
+
+	pub const fn user_code_segment() -> Self {
+		Self(DescriptorFlags::USER_CODE.bits())
+	}
+
+	pub const fn user_data_segment() -> Self {
+		Self(DescriptorFlags::USER_DATA.bits())
+	}
+
+	/// Creates a descriptor for the given TSS, byte granular.
+	///
+	/// ## Safety
+	///
+	/// `tss` must stay valid as long as the descriptor may be loaded in the task register.
+	pub unsafe fn tss_segment(tss: *const TaskStateSegment) -> Self {
+		let base = tss as u64;
+		let limit = TaskStateSegment::SIZE as u64 - 1;
+		Self(
+			(limit & 0xffff)
+				| (base & 0xff_ffff) << 16
+				| TSS_AVAILABLE << 40
+				| DescriptorFlags::PRESENT.bits()
+				| (limit & 0xf_0000) << 32
+				| (base & 0xff00_0000) << 32
+		)
+	}
+
+	/// Creates a descriptor from its raw value.
+	pub const fn from_raw(value: u64) -> Self {
+		Self(value)
+	}
+
+	pub const fn raw(&self) -> u64 {
+		self.0
+	}
+
+	pub const fn base(&self) -> u32 {
+		((self.0 >> 16) & 0xff_ffff | (self.0 >> 32) & 0xff00_0000) as u32
+	}
+
+	/// The 20-bit limit, in units of 4 KiB if [`DescriptorFlags::GRANULARITY`] is set.
+	pub const fn limit(&self) -> u32 {
+		(self.0 & 0xffff | (self.0 >> 32) & 0xf_0000) as u32
+	}
+
+	pub const fn flags(&self) -> DescriptorFlags {
+		DescriptorFlags::from_bits_truncate(self.0)
+	}
+
+	pub const fn dpl(&self) -> PrivilegeLevel {
+		PrivilegeLevel::from_u16((self.0 >> 45) as u16)
+	}
+
+	/// Human-readable kind of the descriptor, e.g. "kernel code" or "TSS".
+	pub fn name(&self) -> &'static str {
+		let flags = self.flags();
+		if 0 == self.0 {
+			"null"
+		}
+		else if !flags.contains(DescriptorFlags::USER_SEGMENT) {
+			match (self.0 >> 40) & 0xf {
+				TSS_AVAILABLE => "TSS",
+				TSS_BUSY => "TSS (busy)",
+				_ => "system",
+			}
+		}
+		else {
+			match (self.dpl(), flags.contains(DescriptorFlags::EXECUTABLE)) {
+				(PrivilegeLevel::Ring0, true) => "kernel code",
+				(PrivilegeLevel::Ring0, false) => "kernel data",
+				(PrivilegeLevel::Ring3, true) => "user code",
+				(PrivilegeLevel::Ring3, false) => "user data",
+				(_, true) => "code",
+				(_, false) => "data",
+			}
+		}
+	}
+}
+
+impl Debug for Descriptor {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.debug_struct("Descriptor")
+			.field("name", &self.name())
+			.field("base", &format_args!("{:#010x}", self.base()))
+			.field("limit", &format_args!("{:#x}", self.limit()))
+			.field("flags", &self.flags())
+			.finish()
+	}
+}
+
+// ===== GlobalDescriptorTable =====
+
+/// A Global Descriptor Table of at most `MAX` entries, built with [`append`](Self::append).
+///
+/// The table is copied where it is loaded (see [`load_at`](Self::load_at)), so
+/// the builder itself need not outlive the call.
+#[derive(Debug, Clone)]
+pub struct GlobalDescriptorTable<const MAX: usize> {
+	table: [Descriptor; MAX],
+	len: usize,
+}
+
+impl<const MAX: usize> GlobalDescriptorTable<MAX> {
+	/// Creates a table holding only the null descriptor.
+	pub const fn new() -> Self {
+		assert!(0 < MAX && MAX <= 8192, "a GDT holds between 1 and 8192 entries");
+		Self {
+			table: [Descriptor::null(); MAX],
+			len: 1,
+		}
+	}
+
+	/// Adds `entry` to the table and returns a selector for it, whose RPL is the descriptor DPL.
+	///
+	/// Panics if the table is full.
+	pub fn append(&mut self, entry: Descriptor) -> SegmentSelector {
+		assert!(self.len < MAX, "GlobalDescriptorTable::append: table full");
+		self.table[self.len] = entry;
+		self.len += 1;
+		SegmentSelector::new((self.len - 1) as u16, entry.dpl())
+	}
+
+	/// The entries added so far, null descriptor included.
+	pub fn entries(&self) -> &[Descriptor] {
+		&self.table[..self.len]
+	}
+
+	/// Size of the table in bytes.
+	pub fn size(&self) -> usize {
+		self.len * core::mem::size_of::<Descriptor>()
+	}
+
+	/// Copies the table to `addr`, then loads it with `lgdt`.
+	///
+	/// The segment registers keep using the previous descriptors until they
+	/// are reloaded (see `instructions::segmentation`).
+	///
+	/// ## Safety
+	///
+	/// `addr` must point to `self.size()` bytes of writable memory, not used
+	/// for anything else as long as the table is loaded. The selectors currently
+	/// loaded in segment registers must stay valid in the new table.
+	pub unsafe fn load_at(&self, addr: usize) -> () {
+		let pointer = DescriptorTablePointer {
+			limit: (self.size() - 1) as u16,
+			base: addr as u32,
+		};
+		unsafe {
+			core::ptr::copy_nonoverlapping(self.table.as_ptr(), addr as *mut Descriptor, self.len);
+			lgdt(&pointer);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test_case]
+	fn flat_segments_match_access_bytes() {
+		assert_eq!(0x9a, (Descriptor::kernel_code_segment().raw() >> 40) as u8);
+		assert_eq!(0x92, (Descriptor::kernel_data_segment().raw() >> 40) as u8);
+		assert_eq!(0xfa, (Descriptor::user_code_segment().raw() >> 40) as u8);
+		assert_eq!(0xf2, (Descriptor::user_data_segment().raw() >> 40) as u8);
+		assert_eq!(0xfffff, Descriptor::user_data_segment().limit());
+	}
+
+	#[test_case]
+	fn tss_segment_base_and_limit() {
+		let tss = 0x12345678 as *const TaskStateSegment;
+		let descriptor = unsafe { Descriptor::tss_segment(tss) };
+		assert_eq!(0x12345678, descriptor.base());
+		assert_eq!(TaskStateSegment::SIZE as u32 - 1, descriptor.limit());
+		assert_eq!("TSS", descriptor.name());
+	}
+}
@@ -4,7 +4,9 @@ use core::fmt::{self, Debug};
 use core::marker::PhantomData;
 use core::ops::Deref;
 use bit_field::BitField;
+use bitflags::bitflags;
 use volatile::Volatile;
+use crate::arch::x86::instructions::segmentation;
 
 /// A struct describing a pointer to a descriptor table (GDT / IDT).
 /// This is in a format suitable for giving to 'lgdt' or 'lidt'.
@@ -48,15 +50,6 @@ pub fn sidt() -> DescriptorTablePointer {
 	idt
 }
 
-#[inline]
-fn get_reg_code_segment() -> u16 {
-	let segment: u16;
-	unsafe {
-		asm!("mov {0:x}, cs", out(reg) segment, options(nomem, nostack, preserves_flags));
-	}
-	segment
-}
-
 #[derive(Clone, Debug)]
 #[repr(C)]
 #[repr(align(16))]
@@ -70,7 +63,7 @@ pub struct InterruptDescriptorTable {
 	pub invalid_opcode: Entry<HandlerFunc>,
 	pub device_not_available: Entry<HandlerFunc>,
 	pub double_fault: Entry<DivergingHandlerFuncWithErrCode>,
-	coprocessor_segment_overrun: Entry<HandlerFunc>,
+	pub coprocessor_segment_overrun: Entry<HandlerFunc>,
 	pub invalid_tss: Entry<HandlerFuncWithErrCode>,
 	pub segment_not_present: Entry<HandlerFuncWithErrCode>,
 	pub stack_segment_fault: Entry<HandlerFuncWithErrCode>,
@@ -146,16 +139,11 @@ impl InterruptDescriptorTable {
 	/// - `self` always stays at the same memory location. It is recommended to wrap it in
 	///   a `Box`.
 	pub unsafe fn load_unsafe(&self) {
-		{ // DEBUG
-			crate::vga_writeln!(1, "interrupts::IDT.pointer(): {:?}", &self.pointer()).unwrap();
-		}
+		crate::debug!("idt: loading {:?}", &self.pointer());
 		unsafe {
 			lidt(&self.pointer());
 		}
-		{ // DEBUG
-			let idt: crate::arch::x86::structures::idt::DescriptorTablePointer = crate::arch::x86::structures::idt::sidt();
-			crate::vga_writeln!(1, "crate::arch::x86::structures::idt::sidt(): {:?}", idt).unwrap();
-		}
+		crate::debug!("idt: sidt() = {:?}", sidt());
 	}
 
 	/// Creates the descriptor pointer for this table. This pointer can only be
@@ -212,11 +200,14 @@ pub type HandlerFuncWithErrCode = extern "x86-interrupt" fn(InterruptStackFrame,
 /// A page fault handler function that pushes a page fault error code.
 pub type PageFaultHandlerFunc = extern "x86-interrupt" fn(InterruptStackFrame, error_code: PageFaultErrorCode);
 
+// Recent nightlies reject a return type (even `!`) on "x86-interrupt" functions,
+// so the diverging handler types can only document that they must not return.
+
 /// A handler function that must not return, e.g. for a machine check exception.
-pub type DivergingHandlerFunc = extern "x86-interrupt" fn(InterruptStackFrame) -> !;
+pub type DivergingHandlerFunc = extern "x86-interrupt" fn(InterruptStackFrame);
 
 /// A handler function with an error code that must not return, e.g. for a double fault exception.
-pub type DivergingHandlerFuncWithErrCode = extern "x86-interrupt" fn(InterruptStackFrame, error_code: u32) -> !;
+pub type DivergingHandlerFuncWithErrCode = extern "x86-interrupt" fn(InterruptStackFrame, error_code: u32);
 
 /// A general handler function for an interrupt or an exception with the interrupt/exceptions's index and an optional error code.
 pub type GeneralHandlerFunc = fn(InterruptStackFrame, index: u8, error_code: Option<u32>);
@@ -252,11 +243,33 @@ impl<F> Entry<F> {
 		self.pointer_high = (addr >> 16) as u16;
 
 		self.options = EntryOptions::minimal();
-		unsafe { self.options.set_code_selector(get_reg_code_segment()) };
+		unsafe { self.options.set_code_selector(segmentation::cs()) };
+		self.options.set_present(true);
+		crate::trace!("idt: handler at {:#x}, {:?}", addr, &self.options);
+		&mut self.options
+	}
+
+	/// Turns the IDT entry into a task gate: instead of calling a handler on the
+	/// current stack, the CPU switches to the task described by the TSS that
+	/// `tss_selector` refers to, with its own stack and registers.
+	///
+	/// The function returns a mutable reference to the entry's options that allows
+	/// further customization.
+	///
+	/// ## Safety
+	///
+	/// The caller must ensure that `tss_selector` refers to a valid, available
+	/// TSS descriptor in the GDT, and that the task it describes never returns
+	/// to the interrupted one with a wrong stack layout.
+	pub unsafe fn set_task_gate(&mut self, tss_selector: u16) -> &mut EntryOptions {
+		// The offset is ignored: the task resumes at the EIP saved in its TSS
+		self.pointer_low = 0;
+		self.pointer_high = 0;
+
+		self.options = EntryOptions::minimal();
+		unsafe { self.options.set_code_selector(tss_selector) };
+		self.options.set_gate_type(GateType::Task);
 		self.options.set_present(true);
-		{ // DEBUG
-			// crate::vga_writeln!(1, "{:?}", &self.options).unwrap();
-		}
 		&mut self.options
 	}
 
@@ -280,9 +293,6 @@ impl<F: HandlerFuncType> Entry<F> {
 	/// This method is only usable with the `abi_x86_interrupt` feature enabled. Without it, the
 	/// unsafe [`Entry::set_handler_addr`] method has to be used instead.
 	pub fn set_handler_fn(&mut self, handler: F) -> &mut EntryOptions {
-		{ // DEBUG
-			// crate::vga_writeln!(1, "&handler as *const _ as u32: {:#x}", &handler as *const _ as u32).unwrap();
-		}
 		unsafe { self.set_handler_addr(handler.to_virt_addr()) }
 	}
 }
@@ -301,9 +311,6 @@ macro_rules! impl_handler_func_type {
 	($f:ty) => {
 		unsafe impl HandlerFuncType for $f {
 			fn to_virt_addr(self) -> u32 {
-				{ // DEBUG
-					// crate::vga_writeln!(1, "self as u32: {:#x}", self as u32).unwrap();
-				}
 				// Casting a function pointer to u32 is fine, if the pointer
 				// width doesn't exeed 32 bits.
 				self as u32
@@ -315,8 +322,18 @@ macro_rules! impl_handler_func_type {
 impl_handler_func_type!(HandlerFunc);
 impl_handler_func_type!(HandlerFuncWithErrCode);
 impl_handler_func_type!(PageFaultHandlerFunc);
-impl_handler_func_type!(DivergingHandlerFunc);
-impl_handler_func_type!(DivergingHandlerFuncWithErrCode);
+
+/// The kind of gate described by an IDT entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum GateType {
+	/// Switches to the task whose TSS selector is stored in the entry.
+	Task = 0b0101,
+	/// Calls the handler with interrupts disabled.
+	Interrupt = 0b1110,
+	/// Calls the handler, leaving the interrupt flag untouched.
+	Trap = 0b1111,
+}
 
 /// Represents the 4 non-offset bytes of an IDT entry.
 #[repr(C)]
@@ -331,6 +348,7 @@ impl Debug for EntryOptions {
 		f.debug_struct("EntryOptions")
 			.field("code_selector", &self.cs)
 			.field("bits", &format_args!("{:#b}", self.bits))
+			.field("gate_type", &format_args!("{:#06b}", self.bits.get_bits(8..12)))
 			.field("present", &self.present())
 			.finish()
 	}
@@ -362,6 +380,12 @@ impl EntryOptions {
 		self
 	}
 
+	/// Set the gate type, the code selector of a task gate is the selector of its TSS.
+	pub fn set_gate_type(&mut self, gate_type: GateType) -> &mut Self {
+		self.bits.set_bits(8..12, gate_type as u16);
+		self
+	}
+
 	fn present(&self) -> bool {
 		self.bits.get_bit(15)
 	}
@@ -483,11 +507,91 @@ impl Debug for InterruptStackFrameValue {
 	}
 }
 
-/// Describes an page fault error code.
+bitflags! {
+	/// Describes an page fault error code.
+	///
+	/// This structure is defined by the following manual sections:
+	///   * AMD Volume 2: 8.4.2
+	///   * Intel Volume 3A: 4.7
+	#[repr(transparent)]
+	#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy)]
+	pub struct PageFaultErrorCode: u32 {
+		/// If this flag is set, the page fault was caused by a page-protection violation,
+		/// else the page fault was caused by a not-present page.
+		const PROTECTION_VIOLATION = 1;
+		/// If this flag is set, the memory access that caused the page fault was a write.
+		/// Else the access that caused the page fault is a memory read.
+		const CAUSED_BY_WRITE = 1 << 1;
+		/// If this flag is set, an access in user mode (CPL=3) caused the page fault.
+		/// Else an access in supervisor mode (CPL=0, 1, or 2) caused the page fault.
+		const USER_MODE = 1 << 2;
+		/// If this flag is set, the page fault is caused by reading a 1 from a reserved field.
+		const MALFORMED_TABLE = 1 << 3;
+		/// If this flag is set, it indicates that the access that caused the page fault was an
+		/// instruction fetch (only reported with PAE/NX enabled).
+		const INSTRUCTION_FETCH = 1 << 4;
+	}
+}
+
+/// Describes an error code referencing a segment selector, as pushed by
+/// #TS, #NP, #SS and #GP.
 ///
 /// This structure is defined by the following manual sections:
-///   * AMD Volume 2: 8.4.2
-///   * Intel Volume 3A: 4.7
+///   * Intel Volume 3A: 6.13
 #[repr(transparent)]
-#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy)]
-pub struct PageFaultErrorCode(u32);
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SelectorErrorCode {
+	flags: u32,
+}
+
+/// The descriptor table a [`SelectorErrorCode`] refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DescriptorTable {
+	Gdt,
+	Idt,
+	Ldt,
+}
+
+impl SelectorErrorCode {
+	/// Ignores the reserved upper 16 bits.
+	pub const fn new_truncate(value: u32) -> Self {
+		Self {
+			flags: value & 0xffff,
+		}
+	}
+
+	/// If true, the exception occurred during the delivery of an external event
+	/// (an hardware interrupt or an earlier exception).
+	pub fn external(&self) -> bool {
+		self.flags.get_bit(0)
+	}
+
+	/// The descriptor table the index refers to.
+	pub fn descriptor_table(&self) -> DescriptorTable {
+		match self.flags.get_bits(1..3) {
+			0b00 => DescriptorTable::Gdt,
+			0b10 => DescriptorTable::Ldt,
+			_ => DescriptorTable::Idt,
+		}
+	}
+
+	/// The index of the descriptor in the table.
+	pub fn index(&self) -> u32 {
+		self.flags.get_bits(3..16)
+	}
+
+	/// If true, the error code does not refer to any selector (e.g. a #GP not caused by a segment).
+	pub fn is_null(&self) -> bool {
+		0 == self.flags
+	}
+}
+
+impl Debug for SelectorErrorCode {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.debug_struct("SelectorErrorCode")
+			.field("external", &self.external())
+			.field("descriptor_table", &self.descriptor_table())
+			.field("index", &self.index())
+			.finish()
+	}
+}
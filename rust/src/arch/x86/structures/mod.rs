@@ -1,2 +1,4 @@
-
+pub mod gdt;
 pub mod idt;
+pub mod paging;
+pub mod tss;
@@ -0,0 +1,366 @@
+use core::fmt::{self, Debug};
+use core::ops::{Index, IndexMut};
+use bitflags::bitflags;
+use crate::arch::x86::instructions::tlb;
+use crate::memory::{Frame, FrameAllocator, PAGE_SIZE};
+
+// http://css.csail.mit.edu/6.858/2013/readings/i386.pdf -> 5.2 Page Translation
+// https://wiki.osdev.org/Paging
+// https://os.phil-opp.com/edition-1/page-tables/
+
+/// Number of entries in a page directory or a page table.
+pub const ENTRY_COUNT: usize = 1024;
+
+/// Size of the memory covered by a page directory entry (4 MiB).
+pub const HUGE_PAGE_SIZE: usize = PAGE_SIZE * ENTRY_COUNT;
+
+bitflags! {
+	/// Flags of a page directory entry or a page table entry.
+	#[repr(transparent)]
+	#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy)]
+	pub struct PageTableFlags: u32 {
+		/// The entry is valid.
+		const PRESENT = 1;
+		/// Writes are allowed (from ring 0 too, if `Cr0Flags::WRITE_PROTECT` is set).
+		const WRITABLE = 1 << 1;
+		/// Ring 3 accesses are allowed.
+		const USER_ACCESSIBLE = 1 << 2;
+		/// Page-level write-through (PWT).
+		const WRITE_THROUGH = 1 << 3;
+		/// Page-level cache disable (PCD).
+		const NO_CACHE = 1 << 4;
+		/// Set by the CPU when the entry is used for a translation.
+		const ACCESSED = 1 << 5;
+		/// Set by the CPU on a write to the page (page table entries and 4 MiB pages only).
+		const DIRTY = 1 << 6;
+		/// Maps a 4 MiB page (page directory entries only, requires `Cr4Flags::PAGE_SIZE_EXTENSION`).
+		const HUGE_PAGE = 1 << 7;
+		/// Not flushed on CR3 writes (requires `Cr4Flags::PAGE_GLOBAL`).
+		const GLOBAL = 1 << 8;
+	}
+}
+
+// ############################################################################
+// #                              ENTRY                                       #
+// ############################################################################
+
+/// A page directory entry or a page table entry: a 4 KiB aligned physical address and flags.
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct PageTableEntry(u32);
+
+impl PageTableEntry {
+	const ADDRESS_MASK: u32 = !0xfff;
+
+	pub const fn new() -> Self {
+		Self(0)
+	}
+
+	pub fn is_unused(&self) -> bool {
+		0 == self.0
+	}
+
+	pub fn set_unused(&mut self) -> () {
+		self.0 = 0;
+	}
+
+	pub fn flags(&self) -> PageTableFlags {
+		PageTableFlags::from_bits_truncate(self.0)
+	}
+
+	/// Physical address the entry points to (a frame, a 4 MiB page or a page table).
+	pub fn addr(&self) -> usize {
+		(self.0 & Self::ADDRESS_MASK) as usize
+	}
+
+	/// The frame the entry points to, if present.
+	pub fn frame(&self) -> Option<Frame> {
+		if self.flags().contains(PageTableFlags::PRESENT) {
+			Some(Frame::containing_address(self.addr()))
+		}
+		else {
+			None
+		}
+	}
+
+	pub fn set_addr(&mut self, addr: usize, flags: PageTableFlags) -> () {
+		assert!(0 == addr % PAGE_SIZE, "set_addr: {:#x} is not 4 KiB aligned", addr);
+		self.0 = (addr as u32) | flags.bits();
+	}
+
+	pub fn set_frame(&mut self, frame: Frame, flags: PageTableFlags) -> () {
+		self.set_addr(frame.start_address(), flags);
+	}
+
+	pub fn set_flags(&mut self, flags: PageTableFlags) -> () {
+		self.0 = (self.0 & Self::ADDRESS_MASK) | flags.bits();
+	}
+}
+
+impl Debug for PageTableEntry {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.debug_struct("PageTableEntry")
+			.field("addr", &format_args!("{:#x}", self.addr()))
+			.field("flags", &self.flags())
+			.finish()
+	}
+}
+
+// ############################################################################
+// #                              TABLES                                      #
+// ############################################################################
+
+macro_rules! impl_table {
+	($table:ident) => {
+		impl $table {
+			pub const fn new() -> Self {
+				Self {
+					entries: [PageTableEntry::new(); ENTRY_COUNT],
+				}
+			}
+
+			/// Clears every entry.
+			pub fn zero(&mut self) -> () {
+				for entry in self.entries.iter_mut() {
+					entry.set_unused();
+				}
+			}
+
+			pub fn iter(&self) -> impl Iterator<Item = &PageTableEntry> {
+				self.entries.iter()
+			}
+		}
+
+		impl Index<usize> for $table {
+			type Output = PageTableEntry;
+			fn index(&self, idx: usize) -> &PageTableEntry {
+				&self.entries[idx]
+			}
+		}
+
+		impl IndexMut<usize> for $table {
+			fn index_mut(&mut self, idx: usize) -> &mut PageTableEntry {
+				&mut self.entries[idx]
+			}
+		}
+	};
+}
+
+/// The first level table, pointed to by CR3. Each entry maps 4 MiB,
+/// either through a [`PageTable`] or directly as a huge page.
+#[repr(C, align(4096))]
+pub struct PageDirectory {
+	entries: [PageTableEntry; ENTRY_COUNT],
+}
+
+/// The second level table. Each entry maps a 4 KiB page.
+#[repr(C, align(4096))]
+pub struct PageTable {
+	entries: [PageTableEntry; ENTRY_COUNT],
+}
+
+impl_table!(PageDirectory);
+impl_table!(PageTable);
+
+// ############################################################################
+// #                              PAGE                                        #
+// ############################################################################
+
+/// A 4 KiB virtual memory page.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Page {
+	number: usize,
+}
+
+impl Page {
+	/// Returns the page that contains the given virtual address.
+	pub fn containing_address(addr: usize) -> Self {
+		Self {
+			number: addr / PAGE_SIZE,
+		}
+	}
+
+	/// Virtual address of the first byte of the page.
+	pub fn start_address(&self) -> usize {
+		self.number * PAGE_SIZE
+	}
+
+	/// Index of the page directory entry covering this page.
+	pub fn directory_index(&self) -> usize {
+		self.number / ENTRY_COUNT
+	}
+
+	/// Index of the page table entry mapping this page.
+	pub fn table_index(&self) -> usize {
+		self.number % ENTRY_COUNT
+	}
+
+	/// Iterates over the pages from `start` to `end` (both included).
+	pub fn range_inclusive(start: Page, end: Page) -> impl Iterator<Item = Page> + Clone {
+		(start.number..=end.number).map(|number| Page { number })
+	}
+}
+
+// ############################################################################
+// #                              MAPPER                                      #
+// ############################################################################
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MapToError {
+	/// A page table was needed but no frame was left.
+	FrameAllocationFailed,
+	/// The page directory entry is a 4 MiB page.
+	ParentEntryHugePage,
+	/// The page is already mapped to the given frame.
+	PageAlreadyMapped(Frame),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum UnmapError {
+	/// The page directory entry is a 4 MiB page.
+	ParentEntryHugePage,
+	PageNotMapped,
+}
+
+/// Edits a two-level page directory.
+///
+/// Page tables are accessed through their physical address, so every frame
+/// that may hold a page table must be identity-mapped once paging is enabled.
+pub struct Mapper {
+	directory: *mut PageDirectory,
+}
+
+// Only reachable through the lock that owns it
+unsafe impl Send for Mapper {}
+
+impl Mapper {
+	/// ## Safety
+	///
+	/// `directory` must point to a valid (possibly empty) page directory that
+	/// is either in use or about to be, and nobody else must be editing it.
+	pub unsafe fn new(directory: *mut PageDirectory) -> Self {
+		Self { directory }
+	}
+
+	/// Physical address of the page directory, suitable for CR3.
+	pub fn directory_address(&self) -> usize {
+		self.directory as usize
+	}
+
+	fn directory(&self) -> &PageDirectory {
+		unsafe { &*self.directory }
+	}
+
+	fn directory_mut(&mut self) -> &mut PageDirectory {
+		unsafe { &mut *self.directory }
+	}
+
+	fn table(&self, directory_index: usize) -> Option<&PageTable> {
+		let entry = &self.directory()[directory_index];
+		if entry.flags().contains(PageTableFlags::PRESENT) && !entry.flags().contains(PageTableFlags::HUGE_PAGE) {
+			Some(unsafe { &*(entry.addr() as *const PageTable) })
+		}
+		else {
+			None
+		}
+	}
+
+	fn table_mut(&mut self, directory_index: usize) -> Result<Option<&mut PageTable>, ()> {
+		let entry = &self.directory()[directory_index];
+		if !entry.flags().contains(PageTableFlags::PRESENT) {
+			Ok(None)
+		}
+		else if entry.flags().contains(PageTableFlags::HUGE_PAGE) {
+			Err(())
+		}
+		else {
+			Ok(Some(unsafe { &mut *(entry.addr() as *mut PageTable) }))
+		}
+	}
+
+	fn table_create<A: FrameAllocator>(&mut self, directory_index: usize, flags: PageTableFlags, allocator: &mut A) -> Result<&mut PageTable, MapToError> {
+		// Intermediate entries must be at least as permissive as the pages they cover
+		let parent_flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE | (flags & PageTableFlags::USER_ACCESSIBLE);
+		match self.table_mut(directory_index) {
+			Err(()) => return Err(MapToError::ParentEntryHugePage),
+			Ok(Some(_)) => {
+				let entry = &mut self.directory_mut()[directory_index];
+				entry.set_flags(entry.flags() | parent_flags);
+			},
+			Ok(None) => {
+				let frame = allocator.allocate_frame().ok_or(MapToError::FrameAllocationFailed)?;
+				let table: &mut PageTable = unsafe { &mut *(frame.start_address() as *mut PageTable) };
+				table.zero();
+				self.directory_mut()[directory_index].set_frame(frame, parent_flags);
+			},
+		}
+		Ok(self.table_mut(directory_index).ok().flatten().unwrap())
+	}
+
+	/// Maps `page` to `frame`, allocating the page table if needed.
+	pub fn map_to<A: FrameAllocator>(&mut self, page: Page, frame: Frame, flags: PageTableFlags, allocator: &mut A) -> Result<(), MapToError> {
+		let table = self.table_create(page.directory_index(), flags, allocator)?;
+		let entry = &mut table[page.table_index()];
+		if let Some(mapped) = entry.frame() {
+			return Err(MapToError::PageAlreadyMapped(mapped));
+		}
+		entry.set_frame(frame, flags | PageTableFlags::PRESENT);
+		Ok(())
+	}
+
+	/// Maps the page at the same virtual address as `frame`.
+	pub fn identity_map<A: FrameAllocator>(&mut self, frame: Frame, flags: PageTableFlags, allocator: &mut A) -> Result<(), MapToError> {
+		self.map_to(Page::containing_address(frame.start_address()), frame, flags, allocator)
+	}
+
+	/// Maps the 4 MiB region starting at `virt` to the one starting at `phys`
+	/// with a single page directory entry.
+	///
+	/// Both addresses must be 4 MiB aligned, and `Cr4Flags::PAGE_SIZE_EXTENSION`
+	/// must be set before the mapping is used.
+	pub fn map_huge(&mut self, virt: usize, phys: usize, flags: PageTableFlags) -> Result<(), MapToError> {
+		assert!(0 == virt % HUGE_PAGE_SIZE && 0 == phys % HUGE_PAGE_SIZE, "map_huge: addresses must be 4 MiB aligned");
+		let entry = &mut self.directory_mut()[virt / HUGE_PAGE_SIZE];
+		if let Some(mapped) = entry.frame() {
+			return Err(MapToError::PageAlreadyMapped(mapped));
+		}
+		entry.set_addr(phys, flags | PageTableFlags::PRESENT | PageTableFlags::HUGE_PAGE);
+		Ok(())
+	}
+
+	/// Removes the mapping of `page` and returns the frame it was mapped to.
+	///
+	/// The page table itself is kept, even if it becomes empty.
+	pub fn unmap(&mut self, page: Page) -> Result<Frame, UnmapError> {
+		let table = match self.table_mut(page.directory_index()) {
+			Err(()) => return Err(UnmapError::ParentEntryHugePage),
+			Ok(None) => return Err(UnmapError::PageNotMapped),
+			Ok(Some(table)) => table,
+		};
+		let entry = &mut table[page.table_index()];
+		let frame = entry.frame().ok_or(UnmapError::PageNotMapped)?;
+		entry.set_unused();
+		tlb::flush(page.start_address());
+		Ok(frame)
+	}
+
+	/// Returns the frame `page` is mapped to (4 KiB pages only).
+	pub fn translate_page(&self, page: Page) -> Option<Frame> {
+		self.table(page.directory_index())?[page.table_index()].frame()
+	}
+
+	/// Translates a virtual address to the physical address it is mapped to.
+	pub fn translate(&self, addr: usize) -> Option<usize> {
+		let page = Page::containing_address(addr);
+		let entry = &self.directory()[page.directory_index()];
+		if !entry.flags().contains(PageTableFlags::PRESENT) {
+			None
+		}
+		else if entry.flags().contains(PageTableFlags::HUGE_PAGE) {
+			Some(entry.addr() + addr % HUGE_PAGE_SIZE)
+		}
+		else {
+			self.translate_page(page).map(|frame| frame.start_address() + addr % PAGE_SIZE)
+		}
+	}
+}
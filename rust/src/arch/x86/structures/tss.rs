@@ -0,0 +1,118 @@
+use core::arch::asm;
+use super::gdt::SegmentSelector;
+
+// http://css.csail.mit.edu/6.858/2013/readings/i386.pdf -> 7.1 Task State Segment
+// https://wiki.osdev.org/Task_State_Segment
+
+/// The 32-bit Task State Segment.
+///
+/// On a hardware task switch, the CPU saves the state of the running task in
+/// its TSS and loads the state of the new task from the target TSS.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct TaskStateSegment {
+	/// Selector of the previous task, set by the CPU on a nested task switch.
+	pub link: u16,
+	_reserved_link: u16,
+	/// Stack pointers and segments loaded on a privilege change to ring 0, 1 and 2.
+	pub esp0: u32,
+	pub ss0: u16,
+	_reserved_ss0: u16,
+	pub esp1: u32,
+	pub ss1: u16,
+	_reserved_ss1: u16,
+	pub esp2: u32,
+	pub ss2: u16,
+	_reserved_ss2: u16,
+	pub cr3: u32,
+	pub eip: u32,
+	pub eflags: u32,
+	pub eax: u32,
+	pub ecx: u32,
+	pub edx: u32,
+	pub ebx: u32,
+	pub esp: u32,
+	pub ebp: u32,
+	pub esi: u32,
+	pub edi: u32,
+	pub es: u16,
+	_reserved_es: u16,
+	pub cs: u16,
+	_reserved_cs: u16,
+	pub ss: u16,
+	_reserved_ss: u16,
+	pub ds: u16,
+	_reserved_ds: u16,
+	pub fs: u16,
+	_reserved_fs: u16,
+	pub gs: u16,
+	_reserved_gs: u16,
+	pub ldt: u16,
+	_reserved_ldt: u16,
+	/// If set, a debug exception is raised when switching to this task.
+	pub trap: u16,
+	/// Offset of the I/O permission bitmap from the start of the TSS.
+	pub iomap_base: u16,
+}
+
+impl TaskStateSegment {
+	/// Size of the TSS, the descriptor limit is one less.
+	pub const SIZE: usize = core::mem::size_of::<Self>();
+
+	/// Creates a zeroed TSS, without any I/O permission bitmap.
+	pub const fn new() -> Self {
+		Self {
+			link: 0,
+			_reserved_link: 0,
+			esp0: 0,
+			ss0: 0,
+			_reserved_ss0: 0,
+			esp1: 0,
+			ss1: 0,
+			_reserved_ss1: 0,
+			esp2: 0,
+			ss2: 0,
+			_reserved_ss2: 0,
+			cr3: 0,
+			eip: 0,
+			eflags: 0,
+			eax: 0,
+			ecx: 0,
+			edx: 0,
+			ebx: 0,
+			esp: 0,
+			ebp: 0,
+			esi: 0,
+			edi: 0,
+			es: 0,
+			_reserved_es: 0,
+			cs: 0,
+			_reserved_cs: 0,
+			ss: 0,
+			_reserved_ss: 0,
+			ds: 0,
+			_reserved_ds: 0,
+			fs: 0,
+			_reserved_fs: 0,
+			gs: 0,
+			_reserved_gs: 0,
+			ldt: 0,
+			_reserved_ldt: 0,
+			trap: 0,
+			iomap_base: Self::SIZE as u16,
+		}
+	}
+}
+
+/// Load a TSS in the task register.
+///
+/// ## Safety
+///
+/// This function is unsafe because the caller must ensure that the given
+/// selector refers to a valid, available TSS descriptor in the GDT.
+#[inline]
+pub unsafe fn load_tss(selector: SegmentSelector) {
+	unsafe {
+		asm!("ltr {0:x}", in(reg) selector.0, options(nostack, preserves_flags));
+	}
+}
@@ -0,0 +1,163 @@
+use core::fmt;
+use bitflags::bitflags;
+use super::instructions::port::{Port, PortReadOnly};
+
+// https://wiki.osdev.org/Serial_Ports
+// https://www.lammertbies.nl/comm/info/serial-uart
+
+/// Base I/O port of the usual serial ports.
+pub const COM1: u16 = 0x3F8;
+pub const COM2: u16 = 0x2F8;
+pub const COM3: u16 = 0x3E8;
+pub const COM4: u16 = 0x2E8;
+
+/// Frequency of the UART clock divided by 16, i.e. the baud rate for a divisor of 1.
+const MAX_BAUD_RATE: u32 = 115200;
+
+bitflags! {
+	/// Line status register.
+	#[repr(transparent)]
+	#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy)]
+	pub struct LineStatus: u8 {
+		/// A received byte is waiting in the receive buffer (or FIFO).
+		const DATA_READY = 1;
+		const OVERRUN_ERROR = 1 << 1;
+		const PARITY_ERROR = 1 << 2;
+		const FRAMING_ERROR = 1 << 3;
+		const BREAK_INTERRUPT = 1 << 4;
+		/// The transmit holding register can take a new byte.
+		const TRANSMITTER_EMPTY = 1 << 5;
+		/// Nothing left to send at all, shift register included.
+		const TRANSMITTER_IDLE = 1 << 6;
+		const FIFO_ERROR = 1 << 7;
+	}
+}
+
+bitflags! {
+	/// Interrupt enable register.
+	#[repr(transparent)]
+	#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy)]
+	pub struct InterruptEnable: u8 {
+		const RECEIVED_DATA = 1;
+		const TRANSMITTER_EMPTY = 1 << 1;
+		const LINE_STATUS = 1 << 2;
+		const MODEM_STATUS = 1 << 3;
+	}
+}
+
+/// Line control: 8 data bits, no parity, one stop bit.
+const LINE_8N1: u8 = 0x03;
+/// Line control: divisor latch access bit.
+const LINE_DLAB: u8 = 0x80;
+/// FIFO control: enable, clear both FIFOs, interrupt at 14 bytes.
+const FIFO_ENABLE_CLEAR_14: u8 = 0xC7;
+/// Modem control: DTR, RTS, and OUT2 (which gates the IRQ line).
+const MODEM_DTR_RTS_OUT2: u8 = 0x0B;
+/// Modem control: DTR, RTS, OUT1, OUT2 and loopback mode.
+const MODEM_LOOPBACK: u8 = 0x1E;
+/// Byte sent to ourselves in loopback mode.
+const LOOPBACK_PROBE: u8 = 0xAE;
+
+/// Errors reported by [`SerialPort::init`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum InitError {
+	/// The baud rate is zero or does not divide 115200.
+	InvalidBaudRate(u32),
+	/// The loopback test failed: no UART there, or a faulty one.
+	LoopbackFailed,
+}
+
+/// A 16550 UART.
+pub struct SerialPort {
+	/// Receive buffer (read), transmit holding register (write), divisor low byte (DLAB).
+	data: Port<u8>,
+	/// Interrupt enable register, divisor high byte (DLAB).
+	interrupt_enable: Port<u8>,
+	fifo_control: Port<u8>,
+	line_control: Port<u8>,
+	modem_control: Port<u8>,
+	line_status: PortReadOnly<u8>,
+}
+
+impl SerialPort {
+	/// Create an interface for the UART whose registers start at `base` (e.g. [`COM1`]).
+	///
+	/// ## Safety
+	///
+	/// `base` must be the I/O port of a serial port, not used through any other interface.
+	pub const unsafe fn new(base: u16) -> Self {
+		Self {
+			data: Port::new(base),
+			interrupt_enable: Port::new(base + 1),
+			fifo_control: Port::new(base + 2),
+			line_control: Port::new(base + 3),
+			modem_control: Port::new(base + 4),
+			line_status: PortReadOnly::new(base + 5),
+		}
+	}
+
+	/// Programs the baud rate, 8N1 framing and the FIFOs, then checks the
+	/// chip in loopback mode. Interrupts are left disabled.
+	pub fn init(&mut self, baud_rate: u32) -> Result<(), InitError> {
+		if 0 == baud_rate || 0 != MAX_BAUD_RATE % baud_rate {
+			return Err(InitError::InvalidBaudRate(baud_rate));
+		}
+		let divisor = (MAX_BAUD_RATE / baud_rate) as u16;
+		unsafe {
+			self.interrupt_enable.write(0);
+			self.line_control.write(LINE_DLAB);
+			self.data.write(divisor as u8);
+			self.interrupt_enable.write((divisor >> 8) as u8);
+			self.line_control.write(LINE_8N1);
+			self.fifo_control.write(FIFO_ENABLE_CLEAR_14);
+
+			self.modem_control.write(MODEM_LOOPBACK);
+			self.data.write(LOOPBACK_PROBE);
+			if LOOPBACK_PROBE != self.data.read() {
+				return Err(InitError::LoopbackFailed);
+			}
+			self.modem_control.write(MODEM_DTR_RTS_OUT2);
+		}
+		Ok(())
+	}
+
+	/// Selects the events that raise the UART interrupt.
+	pub fn set_interrupts(&mut self, interrupts: InterruptEnable) -> () {
+		unsafe { self.interrupt_enable.write(interrupts.bits()) }
+	}
+
+	pub fn line_status(&mut self) -> LineStatus {
+		LineStatus::from_bits_truncate(unsafe { self.line_status.read() })
+	}
+
+	/// Sends a byte, waiting for the transmitter to be ready.
+	pub fn send(&mut self, byte: u8) -> () {
+		while !self.line_status().contains(LineStatus::TRANSMITTER_EMPTY) {
+			core::hint::spin_loop();
+		}
+		unsafe { self.data.write(byte) }
+	}
+
+	/// Returns the next received byte, if any.
+	pub fn receive(&mut self) -> Option<u8> {
+		if self.line_status().contains(LineStatus::DATA_READY) {
+			Some(unsafe { self.data.read() })
+		}
+		else {
+			None
+		}
+	}
+}
+
+impl fmt::Write for SerialPort {
+	fn write_str(&mut self, s: &str) -> fmt::Result {
+		for byte in s.bytes() {
+			// Terminals expect CRLF line endings
+			if b'\n' == byte {
+				self.send(b'\r');
+			}
+			self.send(byte);
+		}
+		Ok(())
+	}
+}
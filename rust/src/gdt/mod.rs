@@ -0,0 +1,86 @@
+use lazy_static::lazy_static;
+use crate::arch::x86::instructions::segmentation;
+use crate::arch::x86::structures::gdt::{sgdt, Descriptor, GlobalDescriptorTable, SegmentSelector};
+use crate::arch::x86::structures::tss;
+use crate::interrupts::double_fault;
+use crate::vga_println;
+
+/// Physical address the GDT is copied to before being loaded (KFS-2 requirement).
+pub const GDT_ADDR: usize = 0x800;
+
+const GDT_ENTRIES: usize = 9;
+
+/// Bytes used by the GDT at [`GDT_ADDR`].
+pub const GDT_SIZE: usize = GDT_ENTRIES * core::mem::size_of::<Descriptor>();
+
+/// Selectors of the kernel GDT entries.
+#[derive(Debug)]
+#[allow(dead_code)] // The user segments are unused until there is a user mode
+pub struct Selectors {
+	pub kernel_code: SegmentSelector,
+	pub kernel_data: SegmentSelector,
+	pub kernel_stack: SegmentSelector,
+	pub user_code: SegmentSelector,
+	pub user_data: SegmentSelector,
+	pub user_stack: SegmentSelector,
+	/// Task the kernel runs as, loaded in the task register by [`init`].
+	pub kernel_tss: SegmentSelector,
+	/// Task switched to on a double fault (see `interrupts::double_fault`).
+	pub double_fault_tss: SegmentSelector,
+}
+
+lazy_static! {
+	static ref GDT: (GlobalDescriptorTable<GDT_ENTRIES>, Selectors) = {
+		let mut gdt = GlobalDescriptorTable::new();
+		let selectors = Selectors {
+			kernel_code: gdt.append(Descriptor::kernel_code_segment()),
+			kernel_data: gdt.append(Descriptor::kernel_data_segment()),
+			kernel_stack: gdt.append(Descriptor::kernel_data_segment()),
+			user_code: gdt.append(Descriptor::user_code_segment()),
+			user_data: gdt.append(Descriptor::user_data_segment()),
+			user_stack: gdt.append(Descriptor::user_data_segment()),
+			kernel_tss: gdt.append(unsafe { Descriptor::tss_segment(double_fault::KERNEL_TSS.as_ptr()) }),
+			double_fault_tss: gdt.append(unsafe { Descriptor::tss_segment(double_fault::DOUBLE_FAULT_TSS.as_ptr()) }),
+		};
+		(gdt, selectors)
+	};
+}
+
+pub fn selectors() -> &'static Selectors {
+	&GDT.1
+}
+
+/// Copies the kernel GDT to [`GDT_ADDR`], loads it, then reloads every segment register.
+pub fn init() -> () {
+	let (gdt, selectors) = &*GDT;
+	unsafe {
+		gdt.load_at(GDT_ADDR);
+		segmentation::load_cs(selectors.kernel_code);
+		segmentation::load_ds(selectors.kernel_data);
+		segmentation::load_es(selectors.kernel_data);
+		segmentation::load_fs(selectors.kernel_data);
+		segmentation::load_gs(selectors.kernel_data);
+		segmentation::load_ss(selectors.kernel_stack);
+		// The CPU saves the interrupted state in this TSS when switching to another task
+		tss::load_tss(selectors.kernel_tss);
+	}
+}
+
+/// Prints every entry of the loaded GDT, whichever it is.
+pub fn dump() -> () {
+	let pointer = sgdt();
+	let entries = (pointer.limit as usize + 1) / core::mem::size_of::<Descriptor>();
+	let base = pointer.base;
+	vga_println!("GDT at {:#x}: {} entries", base, entries).unwrap();
+	for index in 0..entries {
+		let descriptor = unsafe { core::ptr::read((base as *const Descriptor).add(index)) };
+		vga_println!(
+			"GDT[{}] {:<11} base={:#010x} limit={:#07x} raw={:#018x}",
+			index,
+			descriptor.name(),
+			descriptor.base(),
+			descriptor.limit(),
+			descriptor.raw()
+		).unwrap();
+	}
+}
@@ -0,0 +1,98 @@
+use core::arch::naked_asm;
+use core::cell::UnsafeCell;
+use core::ptr;
+use crate::arch::x86::registers::control::Cr3;
+use crate::arch::x86::structures::idt::InterruptStackFrame;
+use crate::arch::x86::structures::tss::TaskStateSegment;
+use crate::memory::vmalloc::{self, VmFlags};
+use super::exceptions::{ErrorCode, ExceptionReport};
+
+// http://css.csail.mit.edu/6.858/2013/readings/i386.pdf -> 7.3 Task Switching
+// http://css.csail.mit.edu/6.858/2013/readings/i386.pdf -> 9.8.8 Interrupt 8 -- Double Fault
+
+/// Size of the stack the double fault task runs on.
+const STACK_SIZE: usize = 4096 * 4;
+
+/// A TSS the CPU reads and writes on its own during task switches.
+pub struct TaskCell(UnsafeCell<TaskStateSegment>);
+
+// Only written before the task register and the IDT are loaded, then by the CPU
+unsafe impl Sync for TaskCell {}
+
+impl TaskCell {
+	const fn new() -> Self {
+		Self(UnsafeCell::new(TaskStateSegment::new()))
+	}
+
+	pub fn as_ptr(&self) -> *mut TaskStateSegment {
+		self.0.get()
+	}
+}
+
+/// The task the kernel runs as, its state is saved here when a double fault occurs.
+pub static KERNEL_TSS: TaskCell = TaskCell::new();
+
+/// The task a double fault switches to, see [`init`].
+pub static DOUBLE_FAULT_TSS: TaskCell = TaskCell::new();
+
+/// Prepares the double fault task: its own stack (followed by a vmalloc guard page),
+/// the current page directory, and [`double_fault_entry`] as entry point.
+///
+/// Must run after paging is enabled, since the task switch reloads CR3 from the TSS.
+pub fn init() -> () {
+	let stack = vmalloc::vmalloc(STACK_SIZE, VmFlags::WRITABLE);
+	if stack.is_null() {
+		panic!("double_fault::init: cannot allocate the double fault stack");
+	}
+	let stack_top = stack as u32 + STACK_SIZE as u32;
+	let selectors = crate::gdt::selectors();
+	let tss = DOUBLE_FAULT_TSS.as_ptr();
+	unsafe {
+		(*tss).esp0 = stack_top;
+		(*tss).ss0 = selectors.kernel_stack.0;
+		(*tss).esp = stack_top;
+		(*tss).ss = selectors.kernel_stack.0;
+		(*tss).cs = selectors.kernel_code.0;
+		(*tss).ds = selectors.kernel_data.0;
+		(*tss).es = selectors.kernel_data.0;
+		(*tss).fs = selectors.kernel_data.0;
+		(*tss).gs = selectors.kernel_data.0;
+		(*tss).cr3 = Cr3::read();
+		(*tss).eip = double_fault_entry as usize as u32;
+		(*tss).eflags = 1 << 1; // Reserved, always set; interrupts stay disabled
+	}
+}
+
+/// Entry point of the double fault task.
+///
+/// A task switch pushes nothing but the error code on the new stack, so turn it
+/// into a regular `extern "C"` call before running any Rust code.
+#[unsafe(naked)]
+extern "C" fn double_fault_entry() -> ! {
+	naked_asm!(
+		"call {handler}", // the error code becomes the first argument
+		"2:",
+		"hlt",
+		"jmp 2b",
+		handler = sym double_fault_task,
+	);
+}
+
+extern "C" fn double_fault_task(error_code: u32) -> ! {
+	// The faulting state was saved by the CPU in the kernel TSS when switching tasks
+	let kernel = unsafe { ptr::read_volatile(KERNEL_TSS.as_ptr()) };
+	let stack_frame = InterruptStackFrame::new(kernel.eip, kernel.cs, kernel.eflags, kernel.esp, kernel.ss);
+	panic!(
+		"{}\nFaulting task: ESP: {:#010x}  EBP: {:#010x}  SS: {:#06x}\n\
+		EAX: {:#010x}  EBX: {:#010x}  ECX: {:#010x}  EDX: {:#010x}  ESI: {:#010x}  EDI: {:#010x}",
+		ExceptionReport {
+			name: "DOUBLE FAULT",
+			mnemonic: "#DF",
+			vector: 8,
+			stack_frame: &stack_frame,
+			error_code: ErrorCode::Raw(error_code),
+		},
+		kernel.esp, kernel.ebp, kernel.ss,
+		kernel.eax, kernel.ebx, kernel.ecx, kernel.edx, kernel.esi, kernel.edi
+	);
+}
@@ -0,0 +1,232 @@
+use core::fmt;
+use core::sync::atomic::{AtomicU32, Ordering};
+use crate::arch::x86::instructions::segmentation;
+use crate::arch::x86::registers::control::{Cr0, Cr2, Cr3};
+use crate::arch::x86::registers::rflags::RFlags;
+use crate::arch::x86::structures::idt::{InterruptDescriptorTable, InterruptStackFrame, PageFaultErrorCode, SelectorErrorCode};
+
+// https://wiki.osdev.org/Exceptions
+// http://css.csail.mit.edu/6.858/2013/readings/i386.pdf -> 9.8 Exception Conditions
+
+/// How the error code pushed by an exception should be decoded.
+#[derive(Debug, Copy, Clone)]
+pub(super) enum ErrorCode {
+	None,
+	Raw(u32),
+	Selector(SelectorErrorCode),
+	PageFault(PageFaultErrorCode),
+}
+
+/// Everything we can tell about the CPU state when an exception occurred.
+///
+/// The general purpose registers are not available: the `x86-interrupt`
+/// calling convention has already reused them when the handler starts.
+pub(super) struct ExceptionReport<'a> {
+	pub(super) name: &'static str,
+	pub(super) mnemonic: &'static str,
+	pub(super) vector: u8,
+	pub(super) stack_frame: &'a InterruptStackFrame,
+	pub(super) error_code: ErrorCode,
+}
+
+impl fmt::Display for ExceptionReport<'_> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		writeln!(f, "EXCEPTION: {} ({}, vector {})", self.name, self.mnemonic, self.vector)?;
+		match self.error_code {
+			ErrorCode::None => {},
+			ErrorCode::Raw(code) => writeln!(f, "Error code: {:#010x}", code)?,
+			ErrorCode::Selector(code) if code.is_null() => writeln!(f, "Error code: 0 (no selector)")?,
+			ErrorCode::Selector(code) => writeln!(
+				f,
+				"Error code: {:?} index {} (selector {:#06x}){}",
+				code.descriptor_table(),
+				code.index(),
+				code.index() << 3,
+				if code.external() { ", external event" } else { "" }
+			)?,
+			ErrorCode::PageFault(code) => writeln!(f, "Error code: {:?}", code)?,
+		}
+		writeln!(f, "EIP: {:#010x}  CS: {:#06x}", self.stack_frame.instruction_pointer, self.stack_frame.code_segment)?;
+		writeln!(f, "EFLAGS: {:#010x} {:?}", self.stack_frame.cpu_flags, RFlags::from_bits_truncate(self.stack_frame.cpu_flags))?;
+		// ESP and SS are only pushed by the CPU on a privilege level change
+		if 0 != self.stack_frame.code_segment & 0b11 {
+			writeln!(f, "ESP: {:#010x}  SS: {:#06x}", self.stack_frame.stack_pointer, self.stack_frame.stack_segment)?;
+		}
+		writeln!(
+			f,
+			"DS: {:#06x}  ES: {:#06x}  FS: {:#06x}  GS: {:#06x}  SS: {:#06x}",
+			segmentation::ds(),
+			segmentation::es(),
+			segmentation::fs(),
+			segmentation::gs(),
+			segmentation::ss()
+		)?;
+		write!(f, "CR0: {:#010x}  CR2: {:#010x}  CR3: {:#010x}", Cr0::read_raw(), Cr2::read(), Cr3::read())
+	}
+}
+
+// ===== Handlers =====
+
+/// Traps and benign exceptions: report on screen 1, then resume.
+macro_rules! report_handler {
+	($handler:ident, $name:literal, $mnemonic:literal, $vector:literal) => {
+		extern "x86-interrupt" fn $handler(stack_frame: InterruptStackFrame) {
+			let report = ExceptionReport {
+				name: $name,
+				mnemonic: $mnemonic,
+				vector: $vector,
+				stack_frame: &stack_frame,
+				error_code: ErrorCode::None,
+			};
+			crate::vga_writeln!(1, "{}", report).unwrap();
+			let _result: core::fmt::Result = crate::serial_println!("{}", report);
+		}
+	};
+}
+
+/// Faults and aborts: resuming would execute the faulting instruction again.
+macro_rules! panic_handler {
+	($handler:ident, $name:literal, $mnemonic:literal, $vector:literal) => {
+		extern "x86-interrupt" fn $handler(stack_frame: InterruptStackFrame) {
+			panic!("{}", ExceptionReport {
+				name: $name,
+				mnemonic: $mnemonic,
+				vector: $vector,
+				stack_frame: &stack_frame,
+				error_code: ErrorCode::None,
+			});
+		}
+	};
+	($handler:ident, $name:literal, $mnemonic:literal, $vector:literal, $decode:expr) => {
+		extern "x86-interrupt" fn $handler(stack_frame: InterruptStackFrame, error_code: u32) {
+			panic!("{}", ExceptionReport {
+				name: $name,
+				mnemonic: $mnemonic,
+				vector: $vector,
+				stack_frame: &stack_frame,
+				error_code: $decode(error_code),
+			});
+		}
+	};
+}
+
+/// NMIs received since boot.
+static NMI_COUNT: AtomicU32 = AtomicU32::new(0);
+
+pub fn nmi_count() -> u32 {
+	NMI_COUNT.load(Ordering::Relaxed)
+}
+
+/// Only counted: an NMI cannot be masked, so it may arrive while the VGA or
+/// serial lock is held, and reporting it would deadlock.
+extern "x86-interrupt" fn non_maskable_interrupt_handler(_stack_frame: InterruptStackFrame) {
+	NMI_COUNT.fetch_add(1, Ordering::Relaxed);
+}
+
+fn selector(error_code: u32) -> ErrorCode {
+	ErrorCode::Selector(SelectorErrorCode::new_truncate(error_code))
+}
+
+report_handler!(debug_handler, "DEBUG", "#DB", 1);
+report_handler!(breakpoint_handler, "BREAKPOINT", "#BP", 3);
+report_handler!(overflow_handler, "OVERFLOW", "#OF", 4);
+
+panic_handler!(divide_error_handler, "DIVIDE ERROR", "#DE", 0);
+panic_handler!(bound_range_exceeded_handler, "BOUND RANGE EXCEEDED", "#BR", 5);
+panic_handler!(invalid_opcode_handler, "INVALID OPCODE", "#UD", 6);
+panic_handler!(device_not_available_handler, "DEVICE NOT AVAILABLE", "#NM", 7);
+panic_handler!(coprocessor_segment_overrun_handler, "COPROCESSOR SEGMENT OVERRUN", "CSO", 9);
+panic_handler!(invalid_tss_handler, "INVALID TSS", "#TS", 10, selector);
+panic_handler!(segment_not_present_handler, "SEGMENT NOT PRESENT", "#NP", 11, selector);
+panic_handler!(stack_segment_fault_handler, "STACK-SEGMENT FAULT", "#SS", 12, selector);
+panic_handler!(general_protection_fault_handler, "GENERAL PROTECTION FAULT", "#GP", 13, selector);
+panic_handler!(x87_floating_point_handler, "x87 FLOATING-POINT EXCEPTION", "#MF", 16);
+panic_handler!(alignment_check_handler, "ALIGNMENT CHECK", "#AC", 17, ErrorCode::Raw);
+panic_handler!(machine_check_handler, "MACHINE CHECK", "#MC", 18);
+panic_handler!(simd_floating_point_handler, "SIMD FLOATING-POINT EXCEPTION", "#XM", 19);
+panic_handler!(virtualization_handler, "VIRTUALIZATION EXCEPTION", "#VE", 20);
+panic_handler!(cp_protection_handler, "CONTROL PROTECTION EXCEPTION", "#CP", 21, ErrorCode::Raw);
+panic_handler!(hv_injection_handler, "HYPERVISOR INJECTION EXCEPTION", "#HV", 28);
+panic_handler!(vmm_communication_handler, "VMM COMMUNICATION EXCEPTION", "#VC", 29, ErrorCode::Raw);
+panic_handler!(security_exception_handler, "SECURITY EXCEPTION", "#SX", 30, ErrorCode::Raw);
+
+extern "x86-interrupt" fn page_fault_handler(stack_frame: InterruptStackFrame, error_code: PageFaultErrorCode) {
+	let accessed_address: u32 = Cr2::read();
+	let access = if error_code.contains(PageFaultErrorCode::INSTRUCTION_FETCH) {
+		"instruction fetch"
+	}
+	else if error_code.contains(PageFaultErrorCode::CAUSED_BY_WRITE) {
+		"write"
+	}
+	else {
+		"read"
+	};
+	let cause = if error_code.contains(PageFaultErrorCode::MALFORMED_TABLE) {
+		"reserved bit set in a paging structure"
+	}
+	else if error_code.contains(PageFaultErrorCode::PROTECTION_VIOLATION) {
+		"protection violation"
+	}
+	else {
+		"page not present"
+	};
+	let report = ExceptionReport {
+		name: "PAGE FAULT",
+		mnemonic: "#PF",
+		vector: 14,
+		stack_frame: &stack_frame,
+		error_code: ErrorCode::PageFault(error_code),
+	};
+	if !error_code.contains(PageFaultErrorCode::USER_MODE) {
+		panic!("{}\nAccessed address: {:#010x} ({} access, {})", report, accessed_address, access, cause);
+	}
+	crate::vga_writeln!(
+		1,
+		"{}\nAccessed address: {:#010x} ({} access, {}, user mode)",
+		report, accessed_address, access, cause
+	).unwrap();
+	let _result: core::fmt::Result = crate::serial_println!(
+		"{}\nAccessed address: {:#010x} ({} access, {}, user mode)",
+		report, accessed_address, access, cause
+	);
+	// TODO: kill the faulting process once there are some, returning would fault again
+	crate::hlt_loop();
+}
+
+/// Installs a handler for every exception vector.
+pub fn set_handlers(idt: &mut InterruptDescriptorTable) -> () {
+	idt.divide_error.set_handler_fn(divide_error_handler);
+	idt.debug.set_handler_fn(debug_handler);
+	idt.non_maskable_interrupt.set_handler_fn(non_maskable_interrupt_handler);
+	idt.breakpoint.set_handler_fn(breakpoint_handler);
+	idt.overflow.set_handler_fn(overflow_handler);
+	idt.bound_range_exceeded.set_handler_fn(bound_range_exceeded_handler);
+	idt.invalid_opcode.set_handler_fn(invalid_opcode_handler);
+	idt.device_not_available.set_handler_fn(device_not_available_handler);
+	// A double fault often means the stack is unusable (e.g. a kernel stack overflow),
+	// so it is handled by a task with its own stack (see `double_fault`)
+	unsafe { idt.double_fault.set_task_gate(crate::gdt::selectors().double_fault_tss.0) };
+	idt.coprocessor_segment_overrun.set_handler_fn(coprocessor_segment_overrun_handler);
+	idt.invalid_tss.set_handler_fn(invalid_tss_handler);
+	idt.segment_not_present.set_handler_fn(segment_not_present_handler);
+	idt.stack_segment_fault.set_handler_fn(stack_segment_fault_handler);
+	idt.general_protection_fault.set_handler_fn(general_protection_fault_handler);
+	idt.page_fault.set_handler_fn(page_fault_handler);
+	idt.x87_floating_point.set_handler_fn(x87_floating_point_handler);
+	idt.alignment_check.set_handler_fn(alignment_check_handler);
+	idt.machine_check.set_handler_fn(machine_check_handler);
+	idt.simd_floating_point.set_handler_fn(simd_floating_point_handler);
+	idt.virtualization.set_handler_fn(virtualization_handler);
+	idt.cp_protection_exception.set_handler_fn(cp_protection_handler);
+	idt.hv_injection_exception.set_handler_fn(hv_injection_handler);
+	idt.vmm_communication_exception.set_handler_fn(vmm_communication_handler);
+	idt.security_exception.set_handler_fn(security_exception_handler);
+}
+
+#[cfg(test)]
+mod tests {
+	#[test_case]
+	fn breakpoint_resumes() {
+		crate::arch::x86::instructions::interrupts::int3();
+	}
+}
@@ -0,0 +1,155 @@
+use core::sync::atomic::{AtomicU32, Ordering};
+use crate::arch::x86::structures::idt::{InterruptDescriptorTable, InterruptStackFrame};
+use crate::sync::IrqSpinlock;
+use super::{_PICS, PIC_1_OFFSET};
+
+// https://www.kernel.org/doc/html/latest/core-api/genericirq.html
+
+/// Lines of the two chained PICs.
+pub const LINES: usize = 16;
+/// Handlers that can share a line.
+const MAX_SHARED: usize = 4;
+
+/// What a handler did with an interrupt.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum IrqReturn {
+	/// The device of the handler raised the interrupt, and was serviced.
+	Handled,
+	/// Not our device: the line is shared.
+	None,
+}
+
+/// Runs in interrupt context, with interrupts disabled. The dispatcher sends
+/// the EOI, handlers must not.
+pub type Handler = fn() -> IrqReturn;
+
+/// Identifies a registered handler, see [`unregister`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct HandlerId {
+	line: u8,
+	slot: usize,
+}
+
+/// Errors reported by [`register`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RegisterError {
+	/// The line is not between 0 and 15.
+	InvalidLine(u8),
+	/// The line already has as many handlers as it can share.
+	LineFull(u8),
+}
+
+static HANDLERS: [IrqSpinlock<[Option<Handler>; MAX_SHARED]>; LINES] = [const { IrqSpinlock::new([None; MAX_SHARED]) }; LINES];
+
+static COUNTS: [AtomicU32; LINES] = [const { AtomicU32::new(0) }; LINES];
+
+/// Spurious IRQ7 and IRQ15 received since boot.
+static SPURIOUS: AtomicU32 = AtomicU32::new(0);
+
+/// Runs `handler` on each interrupt of IRQ `line`, after the handlers that
+/// were registered before it. The line is unmasked.
+pub fn register(line: u8, handler: Handler) -> Result<HandlerId, RegisterError> {
+	if LINES <= line as usize {
+		return Err(RegisterError::InvalidLine(line));
+	}
+	let mut handlers = HANDLERS[line as usize].lock();
+	let slot = handlers.iter().position(|handler| handler.is_none()).ok_or(RegisterError::LineFull(line))?;
+	handlers[slot] = Some(handler);
+	unsafe { _PICS.lock().unmask_irq(line) };
+	Ok(HandlerId { line, slot })
+}
+
+/// Removes a handler. The line is masked once it has none left.
+pub fn unregister(id: HandlerId) -> () {
+	let mut handlers = HANDLERS[id.line as usize].lock();
+	handlers[id.slot] = None;
+	if handlers.iter().all(|handler| handler.is_none()) {
+		unsafe { _PICS.lock().mask_irq(id.line) };
+	}
+}
+
+/// Interrupts received on IRQ `line` since boot, spurious ones excluded.
+pub fn count(line: u8) -> u32 {
+	COUNTS[line as usize].load(Ordering::Relaxed)
+}
+
+pub fn spurious_count() -> u32 {
+	SPURIOUS.load(Ordering::Relaxed)
+}
+
+/// Common part of every IRQ: runs the handlers of the line, then sends the EOI.
+fn dispatch(line: u8) -> () {
+	let interrupt_id = PIC_1_OFFSET + line;
+	if unsafe { _PICS.lock().is_spurious(interrupt_id) } {
+		SPURIOUS.fetch_add(1, Ordering::Relaxed);
+		crate::trace!("irq: spurious interrupt on IRQ{}", line);
+	}
+	else {
+		COUNTS[line as usize].fetch_add(1, Ordering::Relaxed);
+		// Copied, so that handlers may (un)register handlers
+		let handlers = *HANDLERS[line as usize].lock();
+		let handled = handlers.iter().flatten().fold(false, |handled, handler| {
+			IrqReturn::Handled == handler() || handled
+		});
+		if !handled {
+			crate::trace!("irq: unhandled interrupt on IRQ{}", line);
+		}
+	}
+	// Only acknowledges what needs to be, for a spurious interrupt
+	unsafe {
+		_PICS.lock().notify_end_of_interrupt(interrupt_id);
+	}
+}
+
+macro_rules! irq_stubs {
+	($($line:literal => $stub:ident),* $(,)?) => {
+		$(
+			extern "x86-interrupt" fn $stub(_stack_frame: InterruptStackFrame)
+			{
+				dispatch($line);
+			}
+		)*
+
+		/// Routes the 16 IRQ vectors to [`dispatch`].
+		pub(super) fn set_handlers(idt: &mut InterruptDescriptorTable) -> () {
+			$(
+				idt.interrupts[$line].set_handler_fn($stub);
+			)*
+		}
+	};
+}
+
+irq_stubs!(
+	0 => irq0, 1 => irq1, 2 => irq2, 3 => irq3,
+	4 => irq4, 5 => irq5, 6 => irq6, 7 => irq7,
+	8 => irq8, 9 => irq9, 10 => irq10, 11 => irq11,
+	12 => irq12, 13 => irq13, 14 => irq14, 15 => irq15,
+);
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn handled() -> IrqReturn {
+		IrqReturn::Handled
+	}
+
+	#[test_case]
+	fn register_and_unregister() {
+		assert_eq!(Err(RegisterError::InvalidLine(16)), register(16, handled));
+		let ids: [HandlerId; MAX_SHARED] = core::array::from_fn(|_| register(5, handled).unwrap());
+		assert_eq!(Err(RegisterError::LineFull(5)), register(5, handled));
+		for id in ids {
+			unregister(id);
+		}
+		let [mask1, _] = unsafe { _PICS.lock().read_masks() };
+		assert_ne!(0, mask1 & (1 << 5));
+	}
+
+	#[test_case]
+	fn timer_interrupts_are_counted() {
+		let start = count(0);
+		crate::time::sleep_ms(10);
+		assert!(start < count(0));
+	}
+}
@@ -1,47 +1,66 @@
 
+pub mod double_fault;
+mod exceptions;
+pub mod irq;
+
+pub use exceptions::nmi_count;
+
 use lazy_static::lazy_static;
-use crate::arch::x86::structures::idt::{InterruptDescriptorTable, InterruptStackFrame};
+use crate::arch::x86::structures::idt::InterruptDescriptorTable;
 use crate::arch::x86::pic_8259::ChainedPics;
-use crate::keyboard;
+use crate::sync::IrqSpinlock;
 
 lazy_static! {
 	static ref IDT: InterruptDescriptorTable = {
 		let mut idt = InterruptDescriptorTable::new();
-		idt.breakpoint.set_handler_fn(breakpoint_handler);
-		idt.interrupts[0].set_handler_fn(timer_handler);
-		idt.interrupts[1].set_handler_fn(keyboard_handler);
+		exceptions::set_handlers(&mut idt);
+		irq::set_handlers(&mut idt);
 		idt
 	};
 }
 
 pub fn init_idt() -> () {
+	double_fault::init();
 	IDT.load();
 }
 
 const PIC_1_OFFSET: u8 = 32;
 const PIC_2_OFFSET: u8 = PIC_1_OFFSET + 8;
 
-pub static _PICS: spin::Mutex<ChainedPics> = spin::Mutex::new(unsafe { ChainedPics::new(PIC_1_OFFSET, PIC_2_OFFSET) });
-
-
-
-extern "x86-interrupt" fn breakpoint_handler(stack_frame: InterruptStackFrame)
-{
-	crate::vga_writeln!(1, "EXCEPTION: BREAKPOINT\n{:#?}", stack_frame).unwrap(); // TODO: print on serial port
-}
+pub static _PICS: IrqSpinlock<ChainedPics> = IrqSpinlock::new(unsafe { ChainedPics::new(PIC_1_OFFSET, PIC_2_OFFSET) });
 
-extern "x86-interrupt" fn timer_handler(_stack_frame: InterruptStackFrame)
-{
-	// crate::vga_write!(2, ".").unwrap();
+/// Remaps the PICs, with every line masked: lines are unmasked as drivers
+/// register their handlers (see [`irq::register`]).
+pub fn init_pics() -> () {
 	unsafe {
-		_PICS.lock().notify_end_of_interrupt(32);
+		_PICS.lock().initialize();
 	}
 }
 
-extern "x86-interrupt" fn keyboard_handler(_stack_frame: InterruptStackFrame)
-{
-	keyboard::_KB.print_scancode();
-	unsafe {
-		_PICS.lock().notify_end_of_interrupt(33);
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test_case]
+	fn nothing_in_service_outside_handlers() {
+		let mut pics = _PICS.lock();
+		assert_eq!(0, unsafe { pics.read_isr() });
+		assert!(unsafe { pics.is_spurious(PIC_1_OFFSET + 7) });
+		assert!(unsafe { pics.is_spurious(PIC_2_OFFSET + 7) });
+		assert!(!unsafe { pics.is_spurious(PIC_1_OFFSET) });
+	}
+
+	#[test_case]
+	fn mask_and_unmask_irq() {
+		let mut pics = _PICS.lock();
+		let masks = unsafe { pics.read_masks() };
+		unsafe { pics.unmask_irq(12) };
+		let [mask1, mask2] = unsafe { pics.read_masks() };
+		assert_eq!(0, mask1 & (1 << 2));
+		assert_eq!(0, mask2 & (1 << 4));
+		unsafe {
+			pics.mask_irq(12);
+			pics.write_masks(masks[0], masks[1]);
+		}
 	}
 }
@@ -0,0 +1,166 @@
+mod de;
+mod fr;
+mod us;
+
+use super::{KeyCode, Modifiers};
+
+// https://man7.org/linux/man-pages/man5/keymaps.5.html
+
+/// Dead keys are stored as the combining form of their accent.
+pub const DEAD_GRAVE: char = '\u{300}';
+pub const DEAD_ACUTE: char = '\u{301}';
+pub const DEAD_CIRCUMFLEX: char = '\u{302}';
+pub const DEAD_TILDE: char = '\u{303}';
+pub const DEAD_DIAERESIS: char = '\u{308}';
+
+/// What each key types, for a keyboard layout.
+pub struct Keymap {
+    pub name: &'static str,
+    /// Indexed by make code (keys prefixed with 0xE0 are layout independent),
+    /// each row holds the normal, Shift, AltGr and Caps Lock planes of a key.
+    /// `'\0'` types nothing. Characters missing from CP437, which the VGA
+    /// font could not display, are left out.
+    keys: &'static [[char; 4]],
+}
+
+const NORMAL: usize = 0;
+const SHIFT: usize = 1;
+const ALT_GR: usize = 2;
+const CAPS_LOCK: usize = 3;
+
+impl Keymap {
+    /// The character (or dead key) typed by `code` with `modifiers`.
+    ///
+    /// Caps Lock only affects the keys whose Caps Lock plane differs from
+    /// their normal one, and Shift reverses it.
+    pub fn get(&self, code: KeyCode, modifiers: Modifiers) -> Option<char> {
+        let planes = self.keys.get(code as usize)?;
+        let plane = if modifiers.contains(Modifiers::RIGHT_ALT) {
+            ALT_GR
+        }
+        else if modifiers.contains(Modifiers::CAPS_LOCK) && planes[CAPS_LOCK] != planes[NORMAL] {
+            if modifiers.shift() { NORMAL } else { CAPS_LOCK }
+        }
+        else if modifiers.shift() {
+            SHIFT
+        }
+        else {
+            NORMAL
+        };
+        Some(planes[plane]).filter(|&c| '\0' != c)
+    }
+}
+
+static KEYMAPS: [&Keymap; 3] = [&us::US, &fr::FR, &de::DE];
+
+static CURRENT: spin::Mutex<&'static Keymap> = spin::Mutex::new(&us::US);
+
+pub fn find(name: &str) -> Option<&'static Keymap> {
+    KEYMAPS.iter().copied().find(|keymap| name == keymap.name)
+}
+
+pub fn names() -> impl Iterator<Item = &'static str> {
+    KEYMAPS.iter().map(|keymap| keymap.name)
+}
+
+pub fn current() -> &'static Keymap {
+    *CURRENT.lock()
+}
+
+pub fn set(keymap: &'static Keymap) -> () {
+    *CURRENT.lock() = keymap;
+}
+
+pub fn is_dead(c: char) -> bool {
+    matches!(c, DEAD_GRAVE | DEAD_ACUTE | DEAD_CIRCUMFLEX | DEAD_TILDE | DEAD_DIAERESIS)
+}
+
+/// The accent alone, typed when a dead key is not followed by a letter it
+/// combines with. Acute and diaeresis have no spacing form in CP437, so their
+/// closest ASCII look-alikes are used.
+pub fn spacing(dead: char) -> char {
+    match dead {
+        DEAD_GRAVE => '`',
+        DEAD_ACUTE => '\'',
+        DEAD_CIRCUMFLEX => '^',
+        DEAD_TILDE => '~',
+        DEAD_DIAERESIS => '"',
+        _ => dead,
+    }
+}
+
+/// Only the letters CP437 has are composed.
+const COMPOSITIONS: &[(char, &str, &str)] = &[
+    (DEAD_GRAVE, "aeiou", "àèìòù"),
+    (DEAD_ACUTE, "aeiouE", "áéíóúÉ"),
+    (DEAD_CIRCUMFLEX, "aeiou", "âêîôû"),
+    (DEAD_TILDE, "nN", "ñÑ"),
+    (DEAD_DIAERESIS, "aeiouyAOU", "äëïöüÿÄÖÜ"),
+];
+
+/// The character typed by `c` after the dead key `dead`. Space, or the dead
+/// key again, types the accent alone.
+pub fn compose(dead: char, c: char) -> Option<char> {
+    if ' ' == c || dead == c {
+        return Some(spacing(dead));
+    }
+    let (_, bases, composed) = COMPOSITIONS.iter().find(|(accent, _, _)| dead == *accent)?;
+    let index = bases.chars().position(|base| base == c)?;
+    composed.chars().nth(index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn planes_follow_modifiers() {
+        let fr = find("fr").unwrap();
+        assert_eq!(Some('a'), fr.get(KeyCode::Q, Modifiers::empty()));
+        assert_eq!(Some('é'), fr.get(KeyCode::Key2, Modifiers::empty()));
+        assert_eq!(Some('2'), fr.get(KeyCode::Key2, Modifiers::LEFT_SHIFT));
+        assert_eq!(Some(DEAD_TILDE), fr.get(KeyCode::Key2, Modifiers::RIGHT_ALT));
+        assert_eq!(Some('É'), fr.get(KeyCode::Key2, Modifiers::CAPS_LOCK));
+        assert_eq!(Some('é'), fr.get(KeyCode::Key2, Modifiers::CAPS_LOCK | Modifiers::RIGHT_SHIFT));
+        // Not affected by Caps Lock
+        assert_eq!(Some('&'), fr.get(KeyCode::Key1, Modifiers::CAPS_LOCK));
+        assert_eq!(None, fr.get(KeyCode::F1, Modifiers::empty()));
+        assert_eq!(None, fr.get(KeyCode::Up, Modifiers::empty()));
+    }
+
+    #[test_case]
+    fn layouts_have_every_key() {
+        for name in names() {
+            let keymap = find(name).unwrap();
+            assert_eq!(KeyCode::F12 as usize + 1, keymap.keys.len());
+            assert_eq!(Some('\n'), keymap.get(KeyCode::Enter, Modifiers::empty()));
+        }
+        assert_eq!(Some('z'), find("de").unwrap().get(KeyCode::Y, Modifiers::empty()));
+        assert!(find("dvorak").is_none());
+    }
+
+    #[test_case]
+    fn dead_keys_compose() {
+        assert_eq!(Some('ê'), compose(DEAD_CIRCUMFLEX, 'e'));
+        assert_eq!(Some('Ü'), compose(DEAD_DIAERESIS, 'U'));
+        assert_eq!(Some('^'), compose(DEAD_CIRCUMFLEX, ' '));
+        assert_eq!(Some('`'), compose(DEAD_GRAVE, DEAD_GRAVE));
+        assert_eq!(None, compose(DEAD_TILDE, 'x'));
+    }
+
+    #[test_case]
+    fn every_output_can_be_displayed() {
+        let displayable = |c: char| 0xFE != console::cp437::encode(c);
+        for name in names() {
+            for planes in find(name).unwrap().keys {
+                for &c in planes.iter().filter(|&&c| !is_dead(c)) {
+                    assert!(displayable(c), "{}: {:?}", name, c);
+                }
+            }
+        }
+        for (dead, _, composed) in COMPOSITIONS {
+            assert!(displayable(spacing(*dead)));
+            assert!(composed.chars().all(displayable), "{}", composed);
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use super::Keymap;
+
+/// US QWERTY.
+pub static US: Keymap = Keymap {
+    name: "us",
+    keys: &[
+        ['\0', '\0', '\0', '\0'],
+        ['\0', '\0', '\0', '\0'], // esc
+        ['1', '!', '\0', '1'],
+        ['2', '@', '\0', '2'],
+        ['3', '#', '\0', '3'],
+        ['4', '$', '\0', '4'],
+        ['5', '%', '\0', '5'],
+        ['6', '^', '\0', '6'],
+        ['7', '&', '\0', '7'],
+        ['8', '*', '\0', '8'],
+        ['9', '(', '\0', '9'],
+        ['0', ')', '\0', '0'],
+        ['-', '_', '\0', '-'],
+        ['=', '+', '\0', '='],
+        ['\x08', '\x08', '\0', '\x08'], // backspace
+        ['\t', '\t', '\0', '\t'], // tab
+        ['q', 'Q', '\0', 'Q'],
+        ['w', 'W', '\0', 'W'],
+        ['e', 'E', '\0', 'E'],
+        ['r', 'R', '\0', 'R'],
+        ['t', 'T', '\0', 'T'],
+        ['y', 'Y', '\0', 'Y'],
+        ['u', 'U', '\0', 'U'],
+        ['i', 'I', '\0', 'I'],
+        ['o', 'O', '\0', 'O'],
+        ['p', 'P', '\0', 'P'],
+        ['[', '{', '\0', '['],
+        [']', '}', '\0', ']'],
+        ['\n', '\n', '\0', '\n'], // enter
+        ['\0', '\0', '\0', '\0'], // left ctrl
+        ['a', 'A', '\0', 'A'],
+        ['s', 'S', '\0', 'S'],
+        ['d', 'D', '\0', 'D'],
+        ['f', 'F', '\0', 'F'],
+        ['g', 'G', '\0', 'G'],
+        ['h', 'H', '\0', 'H'],
+        ['j', 'J', '\0', 'J'],
+        ['k', 'K', '\0', 'K'],
+        ['l', 'L', '\0', 'L'],
+        [';', ':', '\0', ';'],
+        ['\'', '"', '\0', '\''],
+        ['`', '~', '\0', '`'],
+        ['\0', '\0', '\0', '\0'], // left shift
+        ['\\', '|', '\0', '\\'],
+        ['z', 'Z', '\0', 'Z'],
+        ['x', 'X', '\0', 'X'],
+        ['c', 'C', '\0', 'C'],
+        ['v', 'V', '\0', 'V'],
+        ['b', 'B', '\0', 'B'],
+        ['n', 'N', '\0', 'N'],
+        ['m', 'M', '\0', 'M'],
+        [',', '<', '\0', ','],
+        ['.', '>', '\0', '.'],
+        ['/', '?', '\0', '/'],
+        ['\0', '\0', '\0', '\0'], // right shift
+        ['*', '*', '\0', '*'], // keypad *
+        ['\0', '\0', '\0', '\0'], // left alt
+        [' ', ' ', '\0', ' '], // space
+        ['\0', '\0', '\0', '\0'], // caps lock
+        ['\0', '\0', '\0', '\0'], // f1
+        ['\0', '\0', '\0', '\0'], // f2
+        ['\0', '\0', '\0', '\0'], // f3
+        ['\0', '\0', '\0', '\0'], // f4
+        ['\0', '\0', '\0', '\0'], // f5
+        ['\0', '\0', '\0', '\0'], // f6
+        ['\0', '\0', '\0', '\0'], // f7
+        ['\0', '\0', '\0', '\0'], // f8
+        ['\0', '\0', '\0', '\0'], // f9
+        ['\0', '\0', '\0', '\0'], // f10
+        ['\0', '\0', '\0', '\0'], // num lock
+        ['\0', '\0', '\0', '\0'], // scroll lock
+        ['7', '7', '\0', '7'], // keypad 7
+        ['8', '8', '\0', '8'], // keypad 8
+        ['9', '9', '\0', '9'], // keypad 9
+        ['-', '-', '\0', '-'], // keypad -
+        ['4', '4', '\0', '4'], // keypad 4
+        ['5', '5', '\0', '5'], // keypad 5
+        ['6', '6', '\0', '6'], // keypad 6
+        ['+', '+', '\0', '+'], // keypad +
+        ['1', '1', '\0', '1'], // keypad 1
+        ['2', '2', '\0', '2'], // keypad 2
+        ['3', '3', '\0', '3'], // keypad 3
+        ['0', '0', '\0', '0'], // keypad 0
+        ['.', '.', '\0', '.'], // keypad .
+        ['\0', '\0', '\0', '\0'],
+        ['\0', '\0', '\0', '\0'],
+        ['\\', '|', '\0', '\\'], // 102nd key
+        ['\0', '\0', '\0', '\0'], // f11
+        ['\0', '\0', '\0', '\0'], // f12
+    ],
+};
@@ -1,28 +1,84 @@
 
-use crate::{vga_print, vga_input};
-use crate::arch::x86::instructions::{interrupts, port::Port};
-use core::sync::atomic::{AtomicBool, Ordering};
+pub mod keymap;
+mod scancode;
+
+pub use scancode::{Decoder, KeyCode, KeyEvent, Modifiers};
+
+use crate::{serial_print, vga_print, vga_input};
+use crate::arch::x86::instructions::port::Port;
+use crate::arch::x86::ps2_8042::{Controller, Leds};
 use core::fmt;
 use core::arch::asm;
+use crate::interrupts::irq::{self, IrqReturn};
+use crate::sync::{ByteQueue, IrqSpinlock};
+use core::sync::atomic::{AtomicU8, Ordering};
 
 pub static _KB: Keyboard = Keyboard::new();
 
+/// Commands sent to the keyboard hold it with interrupts disabled, so that
+/// the IRQ1 handler does not take their answers.
+static _PS2: IrqSpinlock<Controller> = IrqSpinlock::new(unsafe { Controller::new() });
+
+/// Scancodes received by the IRQ1 handler, waiting for [`process_pending`].
+static SCANCODES: ByteQueue<128> = ByteQueue::new();
+
+/// Only used outside interrupt context, see [`process_pending`].
 #[derive(Default)]
 pub struct Keyboard {
-    shift: core::sync::atomic::AtomicBool,
-    extended: core::sync::atomic::AtomicBool,
-    ctrl: core::sync::atomic::AtomicBool,
+    decoder: spin::Mutex<Decoder>,
+    /// Dead key waiting for the character it combines with.
+    dead_key: spin::Mutex<Option<char>>,
+    /// LEDs last sent to the keyboard.
+    leds: AtomicU8,
 }
 
-pub struct Key {
-    character :u8,
-    character_uppercase: u8,
+/// Initializes the PS/2 controller, queues the scancodes the keyboard sends on
+/// IRQ1, and applies the `keymap=` option of the command line (a layout name).
+pub fn init(cmdline: Option<&str>) -> () {
+    let result = _PS2.lock().init();
+    match result {
+        Ok(dual_channel) => {
+            crate::info!("ps2: controller initialized, {} port(s)", if dual_channel { 2 } else { 1 });
+            // The controller turned them all off, Num Lock starts on
+            let modifiers = _KB.decoder.lock().modifiers();
+            _KB.update_leds(modifiers);
+        },
+        Err(e) => crate::error!("ps2: initialization failed: {:?}", e),
+    }
+    if let Err(e) = irq::register(1, keyboard_irq) {
+        crate::error!("keyboard: cannot register IRQ1: {:?}", e);
+    }
+    let option = cmdline.and_then(|cmdline| cmdline.split_whitespace().find_map(|arg| arg.strip_prefix("keymap=")));
+    if let Some(name) = option {
+        match keymap::find(name) {
+            Some(keymap) => keymap::set(keymap),
+            None => crate::warn!("cmdline: unknown keymap \"{}\", keeping {}", name, keymap::current().name),
+        }
+    }
 }
 
-impl fmt::Display for Key {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.character as char)
+/// Only reads the scancode: decoding it may run a command, which must not
+/// happen in interrupt context.
+fn keyboard_irq() -> IrqReturn {
+    // The byte may have been taken as the answer to a command already
+    let Some(scancode) = _PS2.lock().receive() else {
+        return IrqReturn::None;
+    };
+    if !SCANCODES.push(scancode) {
+        crate::warn!("keyboard: queue full, scancode dropped");
     }
+    IrqReturn::Handled
+}
+
+/// Decodes the queued scancodes, outside interrupt context.
+pub fn process_pending() -> () {
+    while let Some(scancode) = SCANCODES.pop() {
+        _KB.process_scancode(scancode);
+    }
+}
+
+pub fn has_pending() -> bool {
+    !SCANCODES.is_empty()
 }
 
 pub fn shutdown() {
@@ -35,25 +91,111 @@ pub fn shutdown() {
 }
 
 pub fn reboot() {
-    let mut kbinterface_port: Port<u16> = Port::new(0x64);
-    let mut kbio_port: Port<u16> = Port::new(0x60);
-    let mut tmp:u16 = 0x2;
+    _PS2.lock().reset_system();
+}
 
-    interrupts::without_interrupts(|| {
-        while tmp & 0x2 != 0 {
-            unsafe { tmp = kbinterface_port.read() };
-            if tmp & 1 == 1 {
-                unsafe { tmp |= kbio_port.read() };
-            }
+pub fn clear() {
+    crate::vga::_VGA.clear_display();
+}
+
+/// Input shared by the PS/2 keyboard and the serial port: edits the line being
+/// typed on the current screen (echoed on the serial port), and runs it as a
+/// command on Enter.
+pub fn input(c: char) -> () {
+    if '\n' == c {
+        run_command();
+    }
+    else if '\x08' == c {
+        vga_input!("\x08").unwrap();
+        let _result: fmt::Result = serial_print!("\x08 \x08");
+    }
+    else if !c.is_control() {
+        vga_input!("{}", c).unwrap();
+        let _result: fmt::Result = serial_print!("{}", c);
+    }
+    else {
+        vga_input!("{}", c).unwrap();
+    }
+}
+
+fn run_command() -> () {
+    let cmd: crate::vga::Command = crate::vga::get_command();
+    match cmd {
+        crate::vga::Command::Print_rainbow_42 => crate::vga::print_rainbow_42(),
+        crate::vga::Command::Dump_kernel_stack => dump_kernel_stack(),
+        crate::vga::Command::Clear => clear(),
+        crate::vga::Command::Reboot => reboot(),
+        crate::vga::Command::Shutdown => shutdown(),
+        crate::vga::Command::Dmesg => {
+            crate::vga_println!("").unwrap();
+            crate::log::dmesg();
+        },
+        crate::vga::Command::Uptime => uptime(),
+        crate::vga::Command::Date => date(),
+        crate::vga::Command::Interrupts => print_interrupts(),
+        crate::vga::Command::Loadkeys => loadkeys(),
+        crate::vga::Command::Kbdrate => kbdrate(),
+        _ => crate::vga_println!("").unwrap(),
+    }
+}
+
+fn uptime() -> () {
+    let uptime = crate::time::uptime();
+    crate::vga_println!("").unwrap();
+    crate::vga_println!("up {}.{:03}s ({} ticks)", uptime.as_secs(), uptime.subsec_millis(), crate::time::ticks()).unwrap();
+}
+
+fn date() -> () {
+    crate::vga_println!("").unwrap();
+    crate::vga_println!("{} UTC", crate::time::now()).unwrap();
+}
+
+fn print_interrupts() -> () {
+    use crate::interrupts::irq;
+    crate::vga_println!("").unwrap();
+    for line in 0..irq::LINES as u8 {
+        if 0 != irq::count(line) {
+            crate::vga_println!("IRQ{:<3} {:>10}", line, irq::count(line)).unwrap();
         }
-        unsafe { kbinterface_port.write(0xFE) };
-    });
+    }
+    crate::vga_println!("SPU    {:>10}", irq::spurious_count()).unwrap();
+    crate::vga_println!("NMI    {:>10}", crate::interrupts::nmi_count()).unwrap();
 }
 
-pub fn clear() {
-    interrupts::without_interrupts(|| {
-        crate::vga::_VGA.clear_display();
-    });
+fn loadkeys() -> () {
+    let mut buffer = [0u8; 16];
+    let name = crate::vga::get_argument(0, &mut buffer);
+    crate::vga_println!("").unwrap();
+    if name.is_empty() {
+        crate::vga_print!("{} (available:", keymap::current().name).unwrap();
+        for name in keymap::names() {
+            crate::vga_print!(" {}", name).unwrap();
+        }
+        crate::vga_println!(")").unwrap();
+    }
+    else if let Some(keymap) = keymap::find(name) {
+        keymap::set(keymap);
+        crate::info!("keyboard: switched to the {} keymap", keymap.name);
+    }
+    else {
+        crate::vga_println!("loadkeys: unknown keymap \"{}\"", name).unwrap();
+    }
+}
+
+fn kbdrate() -> () {
+    let mut rate_buffer = [0u8; 8];
+    let mut delay_buffer = [0u8; 8];
+    let rate = crate::vga::get_argument(0, &mut rate_buffer).parse::<u32>();
+    let delay = crate::vga::get_argument(1, &mut delay_buffer).parse::<u32>();
+    crate::vga_println!("").unwrap();
+    let (Ok(rate), Ok(delay)) = (rate, delay) else {
+        crate::vga_println!("usage: kbdrate <rate in cps, 2-30> <delay in ms, 250-1000>").unwrap();
+        return;
+    };
+    match _PS2.lock().set_typematic(rate, delay) {
+        Ok(()) => crate::info!("keyboard: repeat rate {} cps, delay {} ms", rate, delay),
+        Err(e) => crate::vga_println!("kbdrate: {:?}", e).unwrap(),
+    }
 }
 
 fn get_printable_char_from_u32(n: u32) -> char {
@@ -142,208 +284,114 @@ pub fn dump_kernel_stack() -> () {
 }
 
 impl Keyboard {
-    const SCANCODES: &'static [Key] = &[
-        Key {character: b'\0', character_uppercase: b'\0'},
-        Key {character: b'!', character_uppercase: b'!'}, // 1 esc
-        Key {character: b'1', character_uppercase: b'!'},
-        Key {character: b'2', character_uppercase: b'@'},
-        Key {character: b'3', character_uppercase: b'#'},
-        Key {character: b'4', character_uppercase: b'$'},
-        Key {character: b'5', character_uppercase: b'%'},
-        Key {character: b'6', character_uppercase: b'^'},
-        Key {character: b'7', character_uppercase: b'&'},
-        Key {character: b'8', character_uppercase: b'*'},
-        Key {character: b'9', character_uppercase: b'('},
-        Key {character: b'0', character_uppercase: b')'}, // 11
-        Key {character: b'-', character_uppercase: b'_'},
-        Key {character: b'=', character_uppercase: b'+'},
-        Key {character: b'!', character_uppercase: b'!'}, // 14 backspace
-        Key {character: b'\t', character_uppercase: b'\t'}, // 15
-        Key {character: b'q', character_uppercase: b'Q'},
-        Key {character: b'w', character_uppercase: b'W'},
-        Key {character: b'e', character_uppercase: b'E'},
-        Key {character: b'r', character_uppercase: b'R'},
-        Key {character: b't', character_uppercase: b'T'},
-        Key {character: b'y', character_uppercase: b'Y'},
-        Key {character: b'u', character_uppercase: b'U'},
-        Key {character: b'i', character_uppercase: b'I'},
-        Key {character: b'o', character_uppercase: b'O'},
-        Key {character: b'p', character_uppercase: b'P'},
-        Key {character: b'[', character_uppercase: b'{'},
-        Key {character: b']', character_uppercase: b'}'},
-        Key {character: b'\n', character_uppercase: b'\n'}, // 28 enter
-        Key {character: b'!', character_uppercase: b'!'}, // 29 left ctrl
-        Key {character: b'a', character_uppercase: b'A'}, // 30
-        Key {character: b's', character_uppercase: b'S'},
-        Key {character: b'd', character_uppercase: b'D'},
-        Key {character: b'f', character_uppercase: b'F'},
-        Key {character: b'g', character_uppercase: b'G'},
-        Key {character: b'h', character_uppercase: b'H'}, // 35
-        Key {character: b'j', character_uppercase: b'J'},
-        Key {character: b'k', character_uppercase: b'K'},
-        Key {character: b'l', character_uppercase: b'L'},
-        Key {character: b';', character_uppercase: b':'},
-        Key {character: b'\'', character_uppercase: b'\"'}, // 40
-        Key {character: b'`', character_uppercase: b'~'},
-        Key {character: b'!', character_uppercase: b'!'}, // 42 lshift
-        Key {character: b'\\', character_uppercase: b'|'},
-        Key {character: b'z', character_uppercase: b'Z'},
-        Key {character: b'x', character_uppercase: b'X'},
-        Key {character: b'c', character_uppercase: b'C'}, // 45
-        Key {character: b'v', character_uppercase: b'V'},
-        Key {character: b'b', character_uppercase: b'B'},
-        Key {character: b'n', character_uppercase: b'N'},
-        Key {character: b'm', character_uppercase: b'M'}, // 50
-        Key {character: b',', character_uppercase: b'<'},
-        Key {character: b'.', character_uppercase: b'>'},
-        Key {character: b'/', character_uppercase: b'?'},
-        Key {character: b'!', character_uppercase: b'!'}, // right shift
-        Key {character: b'*', character_uppercase: b'*'}, // 55 * keypad
-        Key {character: b'!', character_uppercase: b'!'}, // right shift
-        Key {character: b' ', character_uppercase: b' '}, // space
-        Key {character: b'!', character_uppercase: b'!'}, // capslock
-        Key {character: b'!', character_uppercase: b'!'}, // f1
-        Key {character: b'!', character_uppercase: b'!'}, // 60 f2
-        Key {character: b'!', character_uppercase: b'!'}, // f3
-        Key {character: b'!', character_uppercase: b'!'}, // f4
-        Key {character: b'!', character_uppercase: b'!'}, // f5
-        Key {character: b'!', character_uppercase: b'!'}, // f6
-        Key {character: b'!', character_uppercase: b'!'}, // 65 f7
-        Key {character: b'!', character_uppercase: b'!'}, // f8
-        Key {character: b'!', character_uppercase: b'!'}, // f9
-        Key {character: b'!', character_uppercase: b'!'}, // f10
-        Key {character: b'!', character_uppercase: b'!'}, // numlock
-        Key {character: b'!', character_uppercase: b'!'}, // 70 scrolllock
-        Key {character: b'7', character_uppercase: b'7'}, // keypad 7
-        Key {character: b'8', character_uppercase: b'8'}, // keypad 8
-        Key {character: b'9', character_uppercase: b'9'}, // keypad 9
-        Key {character: b'-', character_uppercase: b'-'}, // keypad -
-        Key {character: b'4', character_uppercase: b'4'}, // 75 keypad 4
-        Key {character: b'5', character_uppercase: b'5'}, // keypad 5
-        Key {character: b'6', character_uppercase: b'6'}, // keypad 6
-        Key {character: b'+', character_uppercase: b'+'}, // keypad +
-        Key {character: b'1', character_uppercase: b'1'}, // keypad 1
-        Key {character: b'2', character_uppercase: b'2'}, // 80 keypad 2
-        Key {character: b'3', character_uppercase: b'3'}, // keypad 3
-        Key {character: b'0', character_uppercase: b'0'}, // keypad 0
-        Key {character: b'.', character_uppercase: b'.'}, // keypad .
-        Key {character: b'!', character_uppercase: b'!'}, // F11
-    ];
-
     pub const fn new() -> Self {
         Self {
-            shift: AtomicBool::new(false),
-            extended: AtomicBool::new(false),
-            ctrl: AtomicBool::new(false),
+            decoder: spin::Mutex::new(Decoder::new()),
+            dead_key: spin::Mutex::new(None),
+            leds: AtomicU8::new(0),
         }
     }
 
-    pub fn print_scancode(&self) -> () {
-        let mut port = Port::new(0x60);
-        let scancode: u8 = unsafe { port.read() };
-        let _real_scancode: u8 = scancode & 0x7f;
-        let _is_pressed: bool = (scancode & 0x80) == 0;
+    pub fn process_scancode(&self, scancode: u8) -> () {
+        // Released before handling the event, which may take a while
+        let event = self.decoder.lock().decode(scancode);
+        if let Some(event) = event {
+            self.update_leds(event.modifiers);
+            if event.pressed {
+                self.process_key_press(event);
+            }
+        }
+    }
 
-        //vga_print!("SCANCODE {:#x} ", scancode).unwrap();
-        if self.ctrl.load(Ordering::Relaxed) == true {
-                if self.shift.load(Ordering::Relaxed) == false {
-                    if _real_scancode == 0x10 { // q
-                        clear();
-                    }
-                    else if _real_scancode == 0x11 { // w
-                        reboot();
-                    }
-                    else if _real_scancode == 0x12 { // e
-                        shutdown();
-                    }
-                    else if _real_scancode == 0x13 { // r
-                        dump_kernel_stack();
-                    }
-                    else if scancode == 0x14 { //t
-                        crate::vga::print_rainbow_42();
-                    }
-                }
-                else {
-                    self.shift.store(false, Ordering::Relaxed);
-                }
-                self.ctrl.store(false, Ordering::Relaxed);
+    fn process_key_press(&self, event: KeyEvent) -> () {
+        let modifiers = event.modifiers;
+        let code = if modifiers.contains(Modifiers::NUM_LOCK) {
+            event.code
         }
-        else if self.extended.load(Ordering::Relaxed) == false {
-            if scancode == 0xE0 {
-                self.extended.store(true, Ordering::Relaxed);
-                //vga_print!("EXTENDED-BYTE ").unwrap();
-            }
-            else if scancode == 42 || scancode == 56 {
-                self.shift.store(true, Ordering::Relaxed);
-            }
-            else if scancode == 29 {
-                self.ctrl.store(true, Ordering::Relaxed);
-            }
-            else if _is_pressed == true {
-                if scancode > 84 {
-                    vga_print!("UNHANDLED SCANCODE {:#x} !", scancode).unwrap();
-                }
-                else if scancode == 14 {
-                    vga_input!("\x08").unwrap(); // BackSpace (ASCII 0x08)
-                }
-                else if scancode == 28 {
-                    let cmd: crate::vga::Command = crate::vga::get_command();
-                    match cmd {
-                        crate::vga::Command::Print_rainbow_42 => crate::vga::print_rainbow_42(),
-                        crate::vga::Command::Dump_kernel_stack => dump_kernel_stack(),
-                        crate::vga::Command::Clear => clear(),
-                        crate::vga::Command::Reboot => reboot(),
-                        crate::vga::Command::Shutdown => shutdown(),
-                        _ => crate::vga_println!("").unwrap(),
-                    }
-                }
-                else {
-                    if 59 <= scancode && scancode <= 66 { // F1 to F8
-                        let value = scancode - 59;
-                        crate::vga::_VGA.set_display(value as usize);
-                    }
-                    else if self.shift.load(Ordering::Relaxed) == false {
-                        vga_input!("{}", Self::SCANCODES[scancode as usize].character as char).unwrap();
-                    }
-                    else {
-                        vga_input!("{}", Self::SCANCODES[scancode as usize].character_uppercase as char).unwrap();
-                        self.shift.store(false, Ordering::Relaxed);
-                    }
-                }
+        else {
+            Self::keypad_navigation(event.code).unwrap_or(event.code)
+        };
+        let keymap = keymap::current();
+
+        if modifiers.ctrl() && !modifiers.shift() {
+            // Shortcuts follow the layout, not the position of the keys
+            match keymap.get(code, Modifiers::empty()) {
+                Some('q') => clear(),
+                Some('w') => reboot(),
+                Some('e') => shutdown(),
+                Some('r') => dump_kernel_stack(),
+                Some('t') => crate::vga::print_rainbow_42(),
+                _ => {},
             }
+            return;
         }
-        else {
-            if (scancode & 0x80) == 0 {
-                if scancode == 0x48 { // arrow up
-                    vga_input!("{}", b'\x18' as char).unwrap();
-                }
-                else if scancode == 0x49 { // pg up
-                    vga_input!("{}", b'\x1e' as char).unwrap();
-                }
-                else if scancode == 0x4b { // arrow left
-                    vga_input!("{}", b'\x1b' as char).unwrap();
-                }
-                else if scancode == 0x4d { // arrow right
-                    vga_input!("{}", b'\x1a' as char).unwrap();
-                }
-                else if scancode == 0x50 { // arrow down
-                    vga_input!("{}", b'\x19' as char).unwrap();
+        match code {
+            KeyCode::F1 | KeyCode::F2 | KeyCode::F3 | KeyCode::F4
+            | KeyCode::F5 | KeyCode::F6 | KeyCode::F7 | KeyCode::F8 => {
+                crate::vga::_VGA.set_display((code as u8 - KeyCode::F1 as u8) as usize);
+            },
+            KeyCode::KeypadEnter => input('\n'),
+            KeyCode::KeypadDivide => input('/'),
+            KeyCode::Up => vga_input!("{}", b'\x18' as char).unwrap(),
+            KeyCode::PageUp => vga_input!("{}", b'\x1e' as char).unwrap(),
+            KeyCode::Left => vga_input!("{}", b'\x1b' as char).unwrap(),
+            KeyCode::Right => vga_input!("{}", b'\x1a' as char).unwrap(),
+            KeyCode::Down => vga_input!("{}", b'\x19' as char).unwrap(),
+            KeyCode::PageDown => vga_input!("{}", b'\x1f' as char).unwrap(),
+            KeyCode::Delete => vga_input!("{}", b'\x7f' as char).unwrap(),
+            _ => {
+                if let Some(c) = keymap.get(code, modifiers) {
+                    self.type_character(c);
                 }
-                else if scancode == 0x51 { // pg down
-                    vga_input!("{}", b'\x1f' as char).unwrap();
-                }
-                else if scancode == 0x53 { // del
-                    vga_input!("{}", b'\x7f' as char).unwrap();
-                }
-                else {
-                    vga_print!("EXTENDED scancode {:#x} pressed", scancode).unwrap();
-                }
-            }
-            else {
-                //vga_print!("Extended scancode {:#x} released", scancode).unwrap();
+            },
+        }
+    }
+
+    /// Lights the LEDs of the lock keys that are on.
+    fn update_leds(&self, modifiers: Modifiers) -> () {
+        let mut leds = Leds::empty();
+        leds.set(Leds::CAPS_LOCK, modifiers.contains(Modifiers::CAPS_LOCK));
+        leds.set(Leds::NUM_LOCK, modifiers.contains(Modifiers::NUM_LOCK));
+        leds.set(Leds::SCROLL_LOCK, modifiers.contains(Modifiers::SCROLL_LOCK));
+        if leds.bits() != self.leds.swap(leds.bits(), Ordering::Relaxed) {
+            if let Err(e) = _PS2.lock().set_leds(leds) {
+                crate::warn!("keyboard: cannot set the LEDs: {:?}", e);
             }
-            self.extended.store(false, Ordering::Relaxed);
         }
     }
-}
 
+    /// What the keypad keys do while Num Lock is off.
+    fn keypad_navigation(code: KeyCode) -> Option<KeyCode> {
+        match code {
+            KeyCode::Keypad7 => Some(KeyCode::Home),
+            KeyCode::Keypad8 => Some(KeyCode::Up),
+            KeyCode::Keypad9 => Some(KeyCode::PageUp),
+            KeyCode::Keypad4 => Some(KeyCode::Left),
+            KeyCode::Keypad6 => Some(KeyCode::Right),
+            KeyCode::Keypad1 => Some(KeyCode::End),
+            KeyCode::Keypad2 => Some(KeyCode::Down),
+            KeyCode::Keypad3 => Some(KeyCode::PageDown),
+            KeyCode::Keypad0 => Some(KeyCode::Insert),
+            KeyCode::KeypadPeriod => Some(KeyCode::Delete),
+            _ => None,
+        }
+    }
+
+    /// Types `c`, or keeps it for the next key if it is a dead key.
+    fn type_character(&self, c: char) -> () {
+        let pending = self.dead_key.lock().take();
+        match pending {
+            None if keymap::is_dead(c) => *self.dead_key.lock() = Some(c),
+            None => input(c),
+            Some(dead) => match keymap::compose(dead, c) {
+                Some(composed) => input(composed),
+                // Enter, Backspace... only cancel the dead key
+                None if c.is_control() => input(c),
+                None => {
+                    input(keymap::spacing(dead));
+                    self.type_character(c);
+                },
+            },
+        }
+    }
+}
@@ -0,0 +1,279 @@
+use bitflags::bitflags;
+
+// https://wiki.osdev.org/PS/2_Keyboard#Scan_Code_Set_1
+// https://www.win.tue.nl/~aeb/linux/kbd/scancodes-1.html
+
+/// Prefix of the keys added by the 101-key keyboard.
+const EXTENDED: u8 = 0xE0;
+/// Prefix of the Pause key, the only one sent with it.
+const PAUSE: u8 = 0xE1;
+/// Set on the break (release) code of a key.
+const BREAK: u8 = 0x80;
+/// Bytes following 0xE1 in the Pause sequence: 1D 45 E1 9D C5.
+const PAUSE_SEQUENCE_LENGTH: u8 = 5;
+
+macro_rules! key_codes {
+    ($($(#[$attr:meta])* $name:ident = $code:literal),* $(,)?) => {
+        /// A physical key, named after its US QWERTY legend. The value is the
+        /// set 1 make code, with bit 7 set for the keys prefixed with 0xE0.
+        #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+        #[repr(u8)]
+        pub enum KeyCode {
+            $($(#[$attr])* $name = $code,)*
+        }
+
+        impl KeyCode {
+            fn from_u8(code: u8) -> Option<Self> {
+                match code {
+                    $($code => Some(Self::$name),)*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+key_codes!(
+    Escape = 0x01,
+    Key1 = 0x02, Key2 = 0x03, Key3 = 0x04, Key4 = 0x05, Key5 = 0x06,
+    Key6 = 0x07, Key7 = 0x08, Key8 = 0x09, Key9 = 0x0A, Key0 = 0x0B,
+    Minus = 0x0C, Equals = 0x0D, Backspace = 0x0E, Tab = 0x0F,
+    Q = 0x10, W = 0x11, E = 0x12, R = 0x13, T = 0x14,
+    Y = 0x15, U = 0x16, I = 0x17, O = 0x18, P = 0x19,
+    LeftBracket = 0x1A, RightBracket = 0x1B, Enter = 0x1C, LeftCtrl = 0x1D,
+    A = 0x1E, S = 0x1F, D = 0x20, F = 0x21, G = 0x22,
+    H = 0x23, J = 0x24, K = 0x25, L = 0x26,
+    Semicolon = 0x27, Quote = 0x28, Backtick = 0x29, LeftShift = 0x2A, Backslash = 0x2B,
+    Z = 0x2C, X = 0x2D, C = 0x2E, V = 0x2F, B = 0x30, N = 0x31, M = 0x32,
+    Comma = 0x33, Period = 0x34, Slash = 0x35, RightShift = 0x36,
+    KeypadMultiply = 0x37, LeftAlt = 0x38, Space = 0x39, CapsLock = 0x3A,
+    F1 = 0x3B, F2 = 0x3C, F3 = 0x3D, F4 = 0x3E, F5 = 0x3F,
+    F6 = 0x40, F7 = 0x41, F8 = 0x42, F9 = 0x43, F10 = 0x44,
+    NumLock = 0x45, ScrollLock = 0x46,
+    Keypad7 = 0x47, Keypad8 = 0x48, Keypad9 = 0x49, KeypadMinus = 0x4A,
+    Keypad4 = 0x4B, Keypad5 = 0x4C, Keypad6 = 0x4D, KeypadPlus = 0x4E,
+    Keypad1 = 0x4F, Keypad2 = 0x50, Keypad3 = 0x51,
+    Keypad0 = 0x52, KeypadPeriod = 0x53,
+    /// The key between Left Shift and Z on 102-key (ISO) keyboards.
+    NonUsBackslash = 0x56,
+    F11 = 0x57, F12 = 0x58,
+    KeypadEnter = 0x9C, RightCtrl = 0x9D, KeypadDivide = 0xB5,
+    PrintScreen = 0xB7,
+    /// AltGr on non-US layouts.
+    RightAlt = 0xB8,
+    Home = 0xC7, Up = 0xC8, PageUp = 0xC9, Left = 0xCB, Right = 0xCD,
+    End = 0xCF, Down = 0xD0, PageDown = 0xD1, Insert = 0xD2, Delete = 0xD3,
+    LeftGui = 0xDB, RightGui = 0xDC, Menu = 0xDD,
+    /// Only ever reported as pressed: the key has no break code.
+    Pause = 0xFF,
+);
+
+bitflags! {
+    /// Modifier keys held, and lock keys toggled on.
+    #[repr(transparent)]
+    #[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+    pub struct Modifiers: u16 {
+        const LEFT_SHIFT = 1;
+        const RIGHT_SHIFT = 1 << 1;
+        const LEFT_CTRL = 1 << 2;
+        const RIGHT_CTRL = 1 << 3;
+        const LEFT_ALT = 1 << 4;
+        const RIGHT_ALT = 1 << 5;
+        const CAPS_LOCK = 1 << 6;
+        const NUM_LOCK = 1 << 7;
+        const SCROLL_LOCK = 1 << 8;
+    }
+}
+
+impl Modifiers {
+    pub fn shift(&self) -> bool {
+        self.intersects(Self::LEFT_SHIFT | Self::RIGHT_SHIFT)
+    }
+
+    pub fn ctrl(&self) -> bool {
+        self.intersects(Self::LEFT_CTRL | Self::RIGHT_CTRL)
+    }
+
+    /// The modifier held with `code`, or the lock toggled by it.
+    fn of(code: KeyCode) -> Option<Self> {
+        match code {
+            KeyCode::LeftShift => Some(Self::LEFT_SHIFT),
+            KeyCode::RightShift => Some(Self::RIGHT_SHIFT),
+            KeyCode::LeftCtrl => Some(Self::LEFT_CTRL),
+            KeyCode::RightCtrl => Some(Self::RIGHT_CTRL),
+            KeyCode::LeftAlt => Some(Self::LEFT_ALT),
+            KeyCode::RightAlt => Some(Self::RIGHT_ALT),
+            KeyCode::CapsLock => Some(Self::CAPS_LOCK),
+            KeyCode::NumLock => Some(Self::NUM_LOCK),
+            KeyCode::ScrollLock => Some(Self::SCROLL_LOCK),
+            _ => None,
+        }
+    }
+
+    fn is_lock(&self) -> bool {
+        Self::CAPS_LOCK.union(Self::NUM_LOCK).union(Self::SCROLL_LOCK).contains(*self)
+    }
+}
+
+/// A key press or release, with the modifiers as they are once it is applied.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub code: KeyCode,
+    pub pressed: bool,
+    pub modifiers: Modifiers,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+enum State {
+    #[default]
+    Start,
+    /// After 0xE0.
+    Extended,
+    /// Inside the Pause sequence, with the bytes left to skip.
+    Pause(u8),
+}
+
+/// Turns the scancode set 1 byte stream into key events.
+#[derive(Debug)]
+pub struct Decoder {
+    state: State,
+    modifiers: Modifiers,
+    /// Lock keys held down, so that typematic repeats do not toggle them again.
+    locks_held: Modifiers,
+}
+
+impl Decoder {
+    /// Num Lock starts on, like most firmwares leave it: the keypad types digits.
+    pub const fn new() -> Self {
+        Self {
+            state: State::Start,
+            modifiers: Modifiers::NUM_LOCK,
+            locks_held: Modifiers::empty(),
+        }
+    }
+
+    pub fn modifiers(&self) -> Modifiers {
+        self.modifiers
+    }
+
+    /// Feeds one byte read from the keyboard. Returns an event once a whole
+    /// sequence has been received, if it is one of a known key.
+    pub fn decode(&mut self, scancode: u8) -> Option<KeyEvent> {
+        let extended = match self.state {
+            State::Pause(1) => {
+                self.state = State::Start;
+                return Some(self.event(KeyCode::Pause, true));
+            },
+            State::Pause(left) => {
+                self.state = State::Pause(left - 1);
+                return None;
+            },
+            State::Start if EXTENDED == scancode => {
+                self.state = State::Extended;
+                return None;
+            },
+            State::Start if PAUSE == scancode => {
+                self.state = State::Pause(PAUSE_SEQUENCE_LENGTH);
+                return None;
+            },
+            State::Start => false,
+            State::Extended => true,
+        };
+        self.state = State::Start;
+        let pressed = 0 == scancode & BREAK;
+        let make = scancode & !BREAK;
+        // Fake shifts sent around Print Screen and the navigation keys
+        if extended && (KeyCode::LeftShift as u8 == make || KeyCode::RightShift as u8 == make) {
+            return None;
+        }
+        let Some(code) = KeyCode::from_u8(if extended { make | BREAK } else { make }) else {
+            crate::trace!("keyboard: unknown scancode {}{:#04x}", if extended { "0xe0 " } else { "" }, scancode);
+            return None;
+        };
+        self.apply(code, pressed);
+        Some(self.event(code, pressed))
+    }
+
+    fn apply(&mut self, code: KeyCode, pressed: bool) -> () {
+        let Some(modifier) = Modifiers::of(code) else {
+            return;
+        };
+        if !modifier.is_lock() {
+            self.modifiers.set(modifier, pressed);
+        }
+        else if !pressed {
+            self.locks_held.remove(modifier);
+        }
+        else if !self.locks_held.contains(modifier) {
+            self.locks_held.insert(modifier);
+            self.modifiers.toggle(modifier);
+        }
+    }
+
+    fn event(&self, code: KeyCode, pressed: bool) -> KeyEvent {
+        KeyEvent { code, pressed, modifiers: self.modifiers }
+    }
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_all(decoder: &mut Decoder, scancodes: &[u8]) -> Option<KeyEvent> {
+        scancodes.iter().fold(None, |_, &scancode| decoder.decode(scancode))
+    }
+
+    #[test_case]
+    fn shift_is_held_until_released() {
+        let mut decoder = Decoder::new();
+        decoder.decode(0x2A);
+        assert_eq!(Modifiers::NUM_LOCK | Modifiers::LEFT_SHIFT, decoder.decode(0x1E).unwrap().modifiers);
+        assert_eq!(Modifiers::NUM_LOCK | Modifiers::LEFT_SHIFT, decoder.decode(0x9E).unwrap().modifiers);
+        let release = decoder.decode(0xAA).unwrap();
+        assert_eq!((KeyCode::LeftShift, false), (release.code, release.pressed));
+        assert_eq!(Modifiers::NUM_LOCK, decoder.decode(0x1E).unwrap().modifiers);
+    }
+
+    #[test_case]
+    fn extended_keys_are_told_apart() {
+        let mut decoder = Decoder::new();
+        assert_eq!(KeyCode::LeftCtrl, decoder.decode(0x1D).unwrap().code);
+        assert_eq!(None, decoder.decode(0xE0));
+        let right_ctrl = decoder.decode(0x1D).unwrap();
+        assert_eq!(KeyCode::RightCtrl, right_ctrl.code);
+        assert!(right_ctrl.modifiers.contains(Modifiers::LEFT_CTRL | Modifiers::RIGHT_CTRL));
+        assert_eq!(KeyCode::Up, decode_all(&mut decoder, &[0xE0, 0x48]).unwrap().code);
+        // Print Screen, with its fake left shift
+        let print_screen = decode_all(&mut decoder, &[0xE0, 0x2A, 0xE0, 0x37]).unwrap();
+        assert_eq!(KeyCode::PrintScreen, print_screen.code);
+        assert!(!print_screen.modifiers.shift());
+    }
+
+    #[test_case]
+    fn pause_sequence_is_a_single_press() {
+        let mut decoder = Decoder::new();
+        let sequence = [0xE1, 0x1D, 0x45, 0xE1, 0x9D, 0xC5];
+        for &scancode in &sequence[..5] {
+            assert_eq!(None, decoder.decode(scancode));
+        }
+        let pause = decoder.decode(sequence[5]).unwrap();
+        assert_eq!((KeyCode::Pause, true), (pause.code, pause.pressed));
+        assert_eq!(Modifiers::NUM_LOCK, pause.modifiers);
+    }
+
+    #[test_case]
+    fn locks_toggle_once_per_press() {
+        let mut decoder = Decoder::new();
+        // Pressed, repeated by the typematic, then released
+        let release = decode_all(&mut decoder, &[0x3A, 0x3A, 0x3A, 0xBA]).unwrap();
+        assert_eq!(Modifiers::NUM_LOCK | Modifiers::CAPS_LOCK, release.modifiers);
+        let release = decode_all(&mut decoder, &[0x3A, 0xBA, 0x45, 0xC5]).unwrap();
+        assert_eq!(Modifiers::empty(), release.modifiers);
+    }
+}
@@ -1,9 +1,25 @@
 
 #![no_std]
+#![cfg_attr(test, no_main)]
 #![feature(abi_x86_interrupt)]
+#![feature(alloc_error_handler)]
+#![feature(custom_test_frameworks)]
+#![test_runner(crate::testing::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+extern crate alloc;
 
 pub mod arch;
-mod interrupts;
+pub mod multiboot2;
+mod gdt;
+pub mod interrupts;
+mod log;
+pub mod memory;
+mod serial;
+mod sync;
+#[cfg(test)]
+mod testing;
+pub mod time;
 mod vga;
 mod keyboard;
 
@@ -14,140 +30,152 @@ pub fn hlt_loop() -> ! {
 	}
 }
 
+/// Processes the input queued by the keyboard and serial interrupt handlers,
+/// sleeping until the next interrupt when there is none.
+fn input_loop() -> ! {
+	use arch::x86::instructions::interrupts;
+	loop {
+		keyboard::process_pending();
+		serial::process_pending();
+		interrupts::disable();
+		if keyboard::has_pending() || serial::has_pending() {
+			interrupts::enable();
+		}
+		else {
+			interrupts::enable_and_hlt();
+		}
+	}
+}
+
 /// This function is called on panic.
+#[cfg(not(test))]
 #[panic_handler]
 fn panic(info: &core::panic::PanicInfo) -> ! {
 	// Meant to be the only occurrence in which screen 0 is allowed
-	let _result: core::fmt::Result = vga_writeln!(0, "\n\n{}", info); // TODO: write on serial port
+	let _result: core::fmt::Result = vga_writeln!(0, "\n\n{}", info);
+	let _result: core::fmt::Result = serial_println!("{}", info);
 	hlt_loop();
 }
 
-use core::arch::asm;
-
-#[repr(C, packed)]
-#[derive(Debug, Clone, Copy)]
-struct DescriptorTablePointer {
-    limit: u16,
-    base: u64,
-}
-
-#[repr(C, packed)]
-#[derive(Debug, Clone, Copy)]
-struct SegmentDescriptor {
-    limit_low: u16,
-    base_low: u16,
-    base_mid: u8,
-    access: u8,
-    flags_limit: u8,
-    base_high: u8,
-}
-
-impl SegmentDescriptor {
-    fn base(&self) -> u32 {
-        (self.base_low as u32)
-            | ((self.base_mid as u32) << 16)
-            | ((self.base_high as u32) << 24)
-    }
-
-    fn limit(&self) -> u32 {
-        (self.limit_low as u32) | (((self.flags_limit & 0x0F) as u32) << 16)
-    }
-
-    fn flags(&self) -> u8 {
-        (self.flags_limit >> 4) & 0x0F
-    }
-
-    const fn empty() -> Self {
-        Self {limit_low: 0, base_low:0, base_mid:0, access:0, flags_limit:0, base_high: 0}
-    }
-    const fn flat(access: u8) -> Self {
-        Self {limit_low: 0xFFFF, base_low:0, base_mid:0, access:access, flags_limit:0xCF, base_high: 0}
-    }
+/// This function is called on panic, while running the tests.
+#[cfg(test)]
+#[panic_handler]
+fn panic(info: &core::panic::PanicInfo) -> ! {
+	testing::panic_handler(info);
 }
 
-pub fn dump_gdt() {
-    let mut gdtr = DescriptorTablePointer { limit: 0, base: 0 };
-
-    unsafe {
-        asm!(
-            "sgdt [{}]",
-            in(reg) &mut gdtr,
-            options(nostack, preserves_flags)
-        );
-    }
-
-    let gdt_size = (gdtr.limit as usize) + 1;
-    let num_entries = gdt_size / core::mem::size_of::<SegmentDescriptor>();
-	vga::_VGA.set_display(7);
-    vga_println!("gdtr.limit: {} gdtr.base {}", gdtr.limit as usize, gdtr.base as usize);
-    vga_println!("size: {} num_entries {}", gdt_size, num_entries);
-
-    let gdt_ptr = gdtr.base as *const SegmentDescriptor;
-    for i in 0..num_entries {
-        let desc = unsafe { *gdt_ptr.add(i) };
-		vga_println!(
-            "GDT[{}]: base=0x{:08X}, limit=0x{:X}, access=0x{:02X}, flags=0x{:02X}",
-            i,
-            desc.base(),
-            desc.limit(),
-            desc.access,
-            desc.flags(),
-        );
-    }
+/// This function is called when the kernel heap cannot satisfy an allocation.
+#[alloc_error_handler]
+fn alloc_error(layout: core::alloc::Layout) -> ! {
+	panic!("allocation error: {:?} (heap break {:#x})", layout, memory::heap::stats().0);
 }
 
-pub fn init_gdt() {
-    let gdtr = DescriptorTablePointer { limit: 7 * core::mem::size_of::<SegmentDescriptor>() as u16 - 1, base: 0x800 };
-    let dest_ptr = 0x800 as *mut SegmentDescriptor;
-    let mut gdt = [SegmentDescriptor::empty(); 7];
-
-    gdt[1] = SegmentDescriptor::flat(0x9A);
-    gdt[2] = SegmentDescriptor::flat(0x92);
-    gdt[3] = SegmentDescriptor::flat(0x92);
-    gdt[4] = SegmentDescriptor::flat(0xFA);
-    gdt[5] = SegmentDescriptor::flat(0xF2);
-    gdt[6] = SegmentDescriptor::flat(0xF2);
-
-    unsafe{
-        core::ptr::copy(gdt.as_ptr(), dest_ptr, 7);
-        asm!("lgdt [{}]",
-             "push 0x8", // push new code segment_descriptor offset on the stack
-             "lea {tmp}, [2f]", // get the address of label 2: (2f == label 2 + look fowrard)
-             "push {tmp}", // put that address on the stack
-             "retf", // pops the 2: address value into EIP and kernel code segment descriptor offset (0x8) in CS register which makes it reload its code cache
-             "2:",
-             "mov ds, ax", // replace all registers address with the address of our data segment
-             "mov es, ax",
-             "mov fs, ax",
-             "mov gs, ax",
-             "mov ss, bx", // replace the stack register by the address of our stack segment
-             in(reg) &gdtr,
-             tmp = out(reg) _,
-             in("ax") 0x10u16,
-             in("bx") 0x18u16,
-             options(readonly, nostack, preserves_flags)
-             );
-    }
+/// Screen the log records are written to.
+static LOG_SCREEN: vga::VgaSink = vga::VgaSink(1);
+
+/// Sends the log records to COM1 and to [`LOG_SCREEN`], then applies the
+/// `loglevel=` option of the command line (a level name or number).
+fn init_log(boot_info: &multiboot2::BootInformation) -> () {
+	log::register_sink(&serial::SerialSink);
+	log::register_sink(&LOG_SCREEN);
+	let option = boot_info.command_line()
+		.and_then(|cmdline| cmdline.split_whitespace().find_map(|arg| arg.strip_prefix("loglevel=")));
+	if let Some(name) = option {
+		match log::Level::from_name(name) {
+			Some(level) => log::set_level(level),
+			None => warn!("cmdline: unknown log level \"{}\", keeping {}", name, log::level()),
+		}
+	}
 }
 
-
-fn init() {
-	init_gdt();
+fn init(boot_info: &multiboot2::BootInformation) {
+	init_log(boot_info);
+	memory::init(boot_info);
+	gdt::init();
 	interrupts::init_idt();
-	unsafe { interrupts::_PICS.lock().initialize() };
+	interrupts::init_pics();
+	serial::init();
+	time::init(time::TIMER_FREQUENCY);
+	keyboard::init(boot_info.command_line());
 
 	arch::x86::instructions::interrupts::enable();
 }
 
+fn print_boot_information(boot_info: &multiboot2::BootInformation) -> () {
+	vga_println!(
+		"multiboot2: {:#x}..{:#x} ({} bytes)",
+		boot_info.start_address(),
+		boot_info.end_address(),
+		boot_info.total_size()
+	).unwrap();
+	if let Some(name) = boot_info.bootloader_name() {
+		vga_println!("bootloader: {}", name).unwrap();
+	}
+	if let Some(cmdline) = boot_info.command_line() {
+		vga_println!("cmdline: \"{}\"", cmdline).unwrap();
+	}
+	if let Some(memory_map) = boot_info.memory_map() {
+		for area in memory_map.all_areas() {
+			vga_println!(
+				"mmap: {:#010x}..{:#010x} {:?}",
+				area.start_address(),
+				area.end_address(),
+				area.typ()
+			).unwrap();
+		}
+	}
+	if let Some(elf_sections) = boot_info.elf_sections() {
+		vga_println!("elf: {} sections", elf_sections.sections().count()).unwrap();
+	}
+	for module in boot_info.modules() {
+		vga_println!("module: {:?}", module).unwrap();
+	}
+	if let Some(framebuffer) = boot_info.framebuffer() {
+		vga_println!("framebuffer: {:?}", framebuffer.typ()).unwrap();
+	}
+}
+
+/// Entry point, called from `_start` (asm/src/boot.s) with the content of
+/// EAX and EBX as left by the bootloader.
 #[no_mangle]
-pub extern "C" fn rust_main(n: u32) {
-	// ATTENTION: we have a very small stack and no guard page
+pub extern "C" fn rust_main(multiboot2_magic: u32, multiboot2_info_addr: u32) {
+	// ATTENTION: we have a very small stack, its guard page is only unmapped once paging is enabled
+
+	let boot_info = match unsafe { multiboot2::BootInformation::load(multiboot2_magic, multiboot2_info_addr) } {
+		Ok(boot_info) => boot_info,
+		Err(e) => panic!("invalid multiboot2 boot information: {:?}", e),
+	};
+
+	#[cfg(test)]
+	{
+		init(&boot_info);
+		test_main();
+	}
 
 	vga::_VGA.set_display(7);
-	vga_println!("\n{}", n).unwrap();
-	dump_gdt();
-	init();
-	dump_gdt();
+	print_boot_information(&boot_info);
+	gdt::dump();
+	init(&boot_info);
+	info!("yak: initialization done");
+	gdt::dump();
+	{
+		let allocator = memory::FRAME_ALLOCATOR.lock();
+		vga_println!(
+			"frames: {} free, {} used, {} total",
+			allocator.free_frames(),
+			allocator.used_frames(),
+			allocator.total_frames()
+		).unwrap();
+	}
+	vga_println!("paging: page directory at {:#x}", arch::x86::registers::control::Cr3::read()).unwrap();
+	{
+		let numbers: alloc::vec::Vec<u32> = (0..8).collect();
+		let (brk, free) = memory::heap::stats();
+		vga_println!("heap: {:?}, break at {:#x}, {} bytes free", numbers, brk, free).unwrap();
+		let area = memory::vmalloc::vmalloc(3 * memory::PAGE_SIZE + 1, memory::vmalloc::VmFlags::WRITABLE);
+		vga_println!("vmalloc: {:p} ({} bytes)", area, memory::vmalloc::vsize(area)).unwrap();
+		unsafe { memory::vmalloc::vfree(area) };
+	}
 	vga_print!("$> ").unwrap();
 	vga_print!("\nThe END").unwrap();
 
@@ -190,6 +218,6 @@ pub extern "C" fn rust_main(n: u32) {
 	// }
 	arch::x86::instructions::interrupts::int3();
 
-	hlt_loop();
+	input_loop();
 }
 
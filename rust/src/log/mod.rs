@@ -0,0 +1,294 @@
+use core::fmt;
+use core::sync::atomic::{AtomicU8, Ordering};
+use core::time::Duration;
+use crate::arch::x86::instructions::interrupts;
+
+// https://www.kernel.org/doc/html/latest/core-api/printk-basics.html
+
+/// Records kept by [`dmesg`], the oldest ones are overwritten first.
+const CAPACITY: usize = 128;
+/// Longer messages are truncated.
+const MESSAGE_SIZE: usize = 120;
+/// Sinks that can be registered at the same time.
+const MAX_SINKS: usize = 4;
+
+// ===== Level =====
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum Level {
+	Error = 1,
+	Warn = 2,
+	Info = 3,
+	Debug = 4,
+	Trace = 5,
+}
+
+impl Level {
+	const ALL: [Self; 5] = [Self::Error, Self::Warn, Self::Info, Self::Debug, Self::Trace];
+
+	pub fn name(&self) -> &'static str {
+		match self {
+			Self::Error => "error",
+			Self::Warn => "warn",
+			Self::Info => "info",
+			Self::Debug => "debug",
+			Self::Trace => "trace",
+		}
+	}
+
+	/// Parses a level from its name or its number (`"debug"` or `"4"`).
+	pub fn from_name(name: &str) -> Option<Self> {
+		Self::ALL.into_iter().find(|level| {
+			name.eq_ignore_ascii_case(level.name()) || name.parse::<u8>() == Ok(*level as u8)
+		})
+	}
+}
+
+impl fmt::Display for Level {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.pad(self.name())
+	}
+}
+
+static LEVEL: AtomicU8 = AtomicU8::new(Level::Info as u8);
+
+/// Messages less severe than this level are discarded.
+pub fn level() -> Level {
+	Level::ALL[LEVEL.load(Ordering::Relaxed) as usize - 1]
+}
+
+pub fn set_level(level: Level) -> () {
+	LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn enabled(level: Level) -> bool {
+	level as u8 <= LEVEL.load(Ordering::Relaxed)
+}
+
+// ===== Record =====
+
+/// A message, as kept in the ring buffer.
+#[derive(Clone, Copy)]
+pub struct Record {
+	pub level: Level,
+	/// Uptime when the message was logged.
+	pub timestamp: Duration,
+	message: [u8; MESSAGE_SIZE],
+	length: usize,
+}
+
+impl Record {
+	const fn empty() -> Self {
+		Self {
+			level: Level::Info,
+			timestamp: Duration::ZERO,
+			message: [0; MESSAGE_SIZE],
+			length: 0,
+		}
+	}
+
+	pub fn message(&self) -> &str {
+		// Truncation happens on a char boundary, see `fmt::Write` below
+		unsafe { core::str::from_utf8_unchecked(&self.message[..self.length]) }
+	}
+}
+
+impl fmt::Write for Record {
+	fn write_str(&mut self, s: &str) -> fmt::Result {
+		let mut end = core::cmp::min(s.len(), MESSAGE_SIZE - self.length);
+		while !s.is_char_boundary(end) {
+			end -= 1;
+		}
+		self.message[self.length..self.length + end].copy_from_slice(&s.as_bytes()[..end]);
+		self.length += end;
+		Ok(())
+	}
+}
+
+impl fmt::Display for Record {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(
+			f,
+			"[{:>5}.{:06}] {:<5} {}",
+			self.timestamp.as_secs(),
+			self.timestamp.subsec_micros(),
+			self.level,
+			self.message()
+		)
+	}
+}
+
+// ===== Ring buffer =====
+
+struct RingBuffer {
+	records: [Record; CAPACITY],
+	/// Index of the oldest record.
+	head: usize,
+	length: usize,
+}
+
+impl RingBuffer {
+	const fn new() -> Self {
+		Self {
+			records: [Record::empty(); CAPACITY],
+			head: 0,
+			length: 0,
+		}
+	}
+
+	fn push(&mut self, record: Record) -> () {
+		if self.length < CAPACITY {
+			self.records[(self.head + self.length) % CAPACITY] = record;
+			self.length += 1;
+		}
+		else {
+			self.records[self.head] = record;
+			self.head = (self.head + 1) % CAPACITY;
+		}
+	}
+
+	fn get(&self, idx: usize) -> Option<&Record> {
+		if idx < self.length {
+			Some(&self.records[(self.head + idx) % CAPACITY])
+		}
+		else {
+			None
+		}
+	}
+}
+
+static BUFFER: spin::Mutex<RingBuffer> = spin::Mutex::new(RingBuffer::new());
+
+// ===== Sinks =====
+
+/// Somewhere log records are written to as they come, besides the ring buffer.
+pub trait Sink: Sync {
+	fn write(&self, record: &Record) -> ();
+}
+
+static SINKS: spin::Mutex<[Option<&'static dyn Sink>; MAX_SINKS]> = spin::Mutex::new([None; MAX_SINKS]);
+
+/// Every record logged from now on is also written to `sink`.
+/// Returns `false` if there is no room left for it.
+pub fn register_sink(sink: &'static dyn Sink) -> bool {
+	interrupts::without_interrupts(|| {
+		let mut sinks = SINKS.lock();
+		if let Some(slot) = sinks.iter_mut().find(|slot| slot.is_none()) {
+			*slot = Some(sink);
+			true
+		}
+		else {
+			false
+		}
+	})
+}
+
+// ===== Macros =====
+
+#[macro_export]
+macro_rules! log {
+	($level:expr, $($arg:tt)*) => {
+		if $crate::log::enabled($level) {
+			$crate::log::_log($level, core::format_args!($($arg)*));
+		}
+	};
+}
+
+#[macro_export]
+macro_rules! error {
+	($($arg:tt)*) => {
+		$crate::log!($crate::log::Level::Error, $($arg)*)
+	};
+}
+
+#[macro_export]
+macro_rules! warn {
+	($($arg:tt)*) => {
+		$crate::log!($crate::log::Level::Warn, $($arg)*)
+	};
+}
+
+#[macro_export]
+macro_rules! info {
+	($($arg:tt)*) => {
+		$crate::log!($crate::log::Level::Info, $($arg)*)
+	};
+}
+
+#[macro_export]
+macro_rules! debug {
+	($($arg:tt)*) => {
+		$crate::log!($crate::log::Level::Debug, $($arg)*)
+	};
+}
+
+#[macro_export]
+macro_rules! trace {
+	($($arg:tt)*) => {
+		$crate::log!($crate::log::Level::Trace, $($arg)*)
+	};
+}
+
+/// Records a message in the ring buffer, then writes it to every sink.
+#[doc(hidden)]
+pub fn _log(level: Level, args: fmt::Arguments) -> () {
+	let mut record = Record::empty();
+	record.level = level;
+	record.timestamp = crate::time::clocksource::monotonic();
+	let _result: fmt::Result = fmt::Write::write_fmt(&mut record, args);
+
+	// Sinks are called without holding any lock, so that they may log themselves
+	let sinks = interrupts::without_interrupts(|| {
+		BUFFER.lock().push(record);
+		*SINKS.lock()
+	});
+	for sink in sinks.iter().flatten() {
+		sink.write(&record);
+	}
+}
+
+/// Replays the ring buffer on the current screen, oldest record first.
+pub fn dmesg() -> () {
+	let mut idx: usize = 0;
+	// The lock is released between records, since printing takes the VGA lock
+	while let Some(record) = interrupts::without_interrupts(|| BUFFER.lock().get(idx).copied()) {
+		let _result: fmt::Result = crate::vga_println!("{}", record);
+		idx += 1;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use core::fmt::Write;
+	use super::*;
+
+	#[test_case]
+	fn ring_buffer_overwrites_oldest() {
+		// Too large for the kernel stack
+		static TEST_BUFFER: spin::Mutex<RingBuffer> = spin::Mutex::new(RingBuffer::new());
+		let mut buffer = TEST_BUFFER.lock();
+		for i in 0..CAPACITY + 2 {
+			let mut record = Record::empty();
+			record.timestamp = Duration::from_secs(i as u64);
+			buffer.push(record);
+		}
+		assert_eq!(Duration::from_secs(2), buffer.get(0).unwrap().timestamp);
+		assert_eq!(Duration::from_secs(CAPACITY as u64 + 1), buffer.get(CAPACITY - 1).unwrap().timestamp);
+		assert!(buffer.get(CAPACITY).is_none());
+	}
+
+	#[test_case]
+	fn record_truncates_on_char_boundary() {
+		let mut record = Record::empty();
+		write!(record, "{}é", "a".repeat(MESSAGE_SIZE - 1)).unwrap();
+		assert_eq!(MESSAGE_SIZE - 1, record.message().len());
+	}
+
+	#[test_case]
+	fn level_from_name() {
+		assert_eq!(Some(Level::Debug), Level::from_name("debug"));
+		assert_eq!(Some(Level::Warn), Level::from_name("2"));
+		assert_eq!(None, Level::from_name("verbose"));
+	}
+}
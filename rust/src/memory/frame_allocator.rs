@@ -0,0 +1,114 @@
+use super::{Frame, FrameAllocator, PAGE_SIZE};
+
+/// Number of frames needed to cover the whole 32-bit physical address space.
+const MAX_FRAMES: usize = u32::MAX as usize / PAGE_SIZE + 1;
+
+/// Number of frames tracked by each word of the bitmap.
+const BITS_PER_WORD: usize = u32::BITS as usize;
+
+const BITMAP_LENGTH: usize = MAX_FRAMES / BITS_PER_WORD;
+
+/// Physical frame allocator keeping one bit per 4 KiB frame.
+///
+/// A set bit means the frame is free. The bitmap is zero-initialized so that
+/// it lives in `.bss` and every frame starts as used: only the areas
+/// explicitly released with [`free_area`](Self::free_area) can be handed out.
+pub struct BitmapFrameAllocator {
+	bitmap: [u32; BITMAP_LENGTH],
+	/// One past the highest frame number ever released.
+	frame_count: usize,
+	free: usize,
+	/// Index of the word at which the next search starts.
+	next: usize,
+}
+
+impl BitmapFrameAllocator {
+	pub const fn new() -> Self {
+		Self {
+			bitmap: [0; BITMAP_LENGTH],
+			frame_count: 0,
+			free: 0,
+			next: 0,
+		}
+	}
+
+	fn is_free(&self, number: usize) -> bool {
+		0 != self.bitmap[number / BITS_PER_WORD] & (1 << (number % BITS_PER_WORD))
+	}
+
+	fn set_free(&mut self, number: usize) -> () {
+		self.bitmap[number / BITS_PER_WORD] |= 1 << (number % BITS_PER_WORD);
+		self.free += 1;
+		if number / BITS_PER_WORD < self.next {
+			self.next = number / BITS_PER_WORD;
+		}
+	}
+
+	fn set_used(&mut self, number: usize) -> () {
+		self.bitmap[number / BITS_PER_WORD] &= !(1 << (number % BITS_PER_WORD));
+		self.free -= 1;
+	}
+
+	/// Marks every frame entirely contained in `start..end` as free.
+	///
+	/// Only whole frames are released: a partial frame at either end stays used.
+	pub fn free_area(&mut self, start: u64, end: u64) -> () {
+		let end = core::cmp::min(end, MAX_FRAMES as u64 * PAGE_SIZE as u64);
+		let first = start.div_ceil(PAGE_SIZE as u64) as usize;
+		let last = (end / PAGE_SIZE as u64) as usize;
+		for number in first..last {
+			if !self.is_free(number) {
+				self.set_free(number);
+			}
+		}
+		if first < last && self.frame_count < last {
+			self.frame_count = last;
+		}
+	}
+
+	/// Marks every frame overlapping `start..end` as used.
+	pub fn reserve_area(&mut self, start: usize, end: usize) -> () {
+		if start < end {
+			let first = start / PAGE_SIZE;
+			let last = core::cmp::min(end.div_ceil(PAGE_SIZE), MAX_FRAMES);
+			for number in first..last {
+				if self.is_free(number) {
+					self.set_used(number);
+				}
+			}
+		}
+	}
+
+	/// Number of frames ready to be allocated.
+	pub fn free_frames(&self) -> usize {
+		self.free
+	}
+
+	/// Number of frames either allocated or reserved, below the highest usable frame.
+	pub fn used_frames(&self) -> usize {
+		self.frame_count - self.free
+	}
+
+	/// Number of frames below the highest usable frame (holes included).
+	pub fn total_frames(&self) -> usize {
+		self.frame_count
+	}
+}
+
+impl FrameAllocator for BitmapFrameAllocator {
+	fn allocate_frame(&mut self) -> Option<Frame> {
+		let words = self.frame_count.div_ceil(BITS_PER_WORD);
+		let idx = (self.next..words).find(|&idx| 0 != self.bitmap[idx])?;
+		let number = idx * BITS_PER_WORD + self.bitmap[idx].trailing_zeros() as usize;
+		self.set_used(number);
+		self.next = idx;
+		Some(Frame { number })
+	}
+
+	fn deallocate_frame(&mut self, frame: Frame) -> () {
+		if frame.number >= self.frame_count || self.is_free(frame.number) {
+			panic!("deallocate_frame: frame {:#x} is not in use", frame.start_address());
+		}
+		self.set_free(frame.number);
+	}
+}
@@ -0,0 +1,361 @@
+use core::alloc::{GlobalAlloc, Layout};
+use core::ptr;
+use crate::arch::x86::instructions::interrupts;
+use crate::arch::x86::structures::paging::{Page, PageTableFlags};
+use super::{paging, FrameAllocator, FRAME_ALLOCATOR, KERNEL_HEAP_MAX_SIZE, KERNEL_HEAP_START, PAGE_SIZE};
+
+// https://os.phil-opp.com/allocator-designs/#linked-list-allocator
+// https://doc.rust-lang.org/stable/alloc/alloc/trait.GlobalAlloc.html
+
+#[global_allocator]
+static ALLOCATOR: KernelHeap = KernelHeap::new();
+
+// ############################################################################
+// #                              HEAP                                        #
+// ############################################################################
+
+/// Header written at the start of every free region.
+struct ListNode {
+	size: usize,
+	next: *mut ListNode,
+}
+
+const NODE_SIZE: usize = core::mem::size_of::<ListNode>();
+const NODE_ALIGN: usize = core::mem::align_of::<ListNode>();
+
+/// First-fit allocator over `KERNEL_HEAP_START..brk`.
+///
+/// Free regions are kept sorted by address in a singly linked list (stored in
+/// the free regions themselves) so that neighbours can be merged back on free.
+/// When no region is large enough, the break is moved up and the new pages are
+/// mapped from the frame allocator.
+pub struct Heap {
+	head: *mut ListNode,
+	brk: usize,
+	/// First byte past the last mapped heap page.
+	mapped_end: usize,
+}
+
+// Only reachable through the lock that owns it
+unsafe impl Send for Heap {}
+
+impl Heap {
+	const fn new() -> Self {
+		Self {
+			head: ptr::null_mut(),
+			brk: KERNEL_HEAP_START,
+			mapped_end: KERNEL_HEAP_START,
+		}
+	}
+
+	/// Moves the break `increment` bytes up, mapping the pages needed to back it,
+	/// and returns the previous break.
+	fn sbrk(&mut self, increment: usize) -> Option<usize> {
+		let old_brk = self.brk;
+		let new_brk = old_brk.checked_add(increment)?;
+		if new_brk > KERNEL_HEAP_START + KERNEL_HEAP_MAX_SIZE {
+			return None;
+		}
+		let mut mapper = paging::MAPPER.lock();
+		let mapper = mapper.as_mut()?;
+		let mut allocator = FRAME_ALLOCATOR.lock();
+		let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE;
+		while self.mapped_end < new_brk {
+			let frame = allocator.allocate_frame()?;
+			if mapper.map_to(Page::containing_address(self.mapped_end), frame, flags, &mut *allocator).is_err() {
+				allocator.deallocate_frame(frame);
+				return None;
+			}
+			self.mapped_end += PAGE_SIZE;
+		}
+		self.brk = new_brk;
+		Some(old_brk)
+	}
+
+	/// Adjusts a layout so that the resulting block can hold a `ListNode` once freed.
+	fn size_align(layout: Layout) -> (usize, usize) {
+		let layout = layout.align_to(NODE_ALIGN).expect("size_align: invalid alignment").pad_to_align();
+		(core::cmp::max(layout.size(), NODE_SIZE), layout.align())
+	}
+
+	/// Returns the address at which `size` bytes aligned on `align` could be carved
+	/// out of `start..end`, leaving either nothing or room for a `ListNode` on both sides.
+	fn alloc_from_region(start: usize, end: usize, size: usize, align: usize) -> Option<usize> {
+		let mut alloc_start = start.next_multiple_of(align);
+		if alloc_start != start && alloc_start - start < NODE_SIZE {
+			alloc_start = (start + NODE_SIZE).next_multiple_of(align);
+		}
+		let alloc_end = alloc_start.checked_add(size)?;
+		if alloc_end > end || (alloc_end < end && end - alloc_end < NODE_SIZE) {
+			return None;
+		}
+		Some(alloc_start)
+	}
+
+	/// Inserts a region in the free list, merging it with adjacent free regions.
+	///
+	/// ## Safety
+	///
+	/// The region must be unused, inside the heap, and at least `NODE_SIZE` bytes long.
+	unsafe fn add_free_region(&mut self, addr: usize, size: usize) -> () {
+		let mut prev: *mut ListNode = ptr::null_mut();
+		let mut current: *mut ListNode = self.head;
+		unsafe {
+			while !current.is_null() && (current as usize) < addr {
+				prev = current;
+				current = (*current).next;
+			}
+			let mut node = ListNode { size, next: current };
+			if !current.is_null() && addr + size == current as usize {
+				node.size += (*current).size;
+				node.next = (*current).next;
+			}
+			if !prev.is_null() && prev as usize + (*prev).size == addr {
+				(*prev).size += node.size;
+				(*prev).next = node.next;
+			}
+			else {
+				let node_ptr = addr as *mut ListNode;
+				node_ptr.write(node);
+				if prev.is_null() {
+					self.head = node_ptr;
+				}
+				else {
+					(*prev).next = node_ptr;
+				}
+			}
+		}
+	}
+
+	/// Looks for a free region able to hold the block and removes the block from it.
+	fn find_region(&mut self, size: usize, align: usize) -> Option<usize> {
+		let mut prev: *mut ListNode = ptr::null_mut();
+		let mut current: *mut ListNode = self.head;
+		while !current.is_null() {
+			let (start, region_size, next) = unsafe { (current as usize, (*current).size, (*current).next) };
+			let end = start + region_size;
+			if let Some(alloc_start) = Self::alloc_from_region(start, end, size, align) {
+				if prev.is_null() {
+					self.head = next;
+				}
+				else {
+					unsafe { (*prev).next = next };
+				}
+				unsafe {
+					if start < alloc_start {
+						self.add_free_region(start, alloc_start - start);
+					}
+					if alloc_start + size < end {
+						self.add_free_region(alloc_start + size, end - (alloc_start + size));
+					}
+				}
+				return Some(alloc_start);
+			}
+			prev = current;
+			current = next;
+		}
+		None
+	}
+
+	fn allocate(&mut self, layout: Layout) -> *mut u8 {
+		let (size, align) = Self::size_align(layout);
+		if let Some(addr) = self.find_region(size, align) {
+			return addr as *mut u8;
+		}
+		// Grow the heap enough to fit the block whatever the alignment, then retry
+		let increment = match size.checked_add(align + NODE_SIZE) {
+			Some(increment) => increment.next_multiple_of(PAGE_SIZE),
+			None => return ptr::null_mut(),
+		};
+		match self.sbrk(increment) {
+			Some(old_brk) => unsafe { self.add_free_region(old_brk, increment) },
+			None => return ptr::null_mut(),
+		}
+		self.find_region(size, align).map_or(ptr::null_mut(), |addr| addr as *mut u8)
+	}
+
+	unsafe fn deallocate(&mut self, ptr: *mut u8, layout: Layout) -> () {
+		let (size, _) = Self::size_align(layout);
+		unsafe { self.add_free_region(ptr as usize, size) }
+	}
+
+	/// Bytes currently available in the free list.
+	fn free_bytes(&self) -> usize {
+		let mut total: usize = 0;
+		let mut current: *const ListNode = self.head;
+		while !current.is_null() {
+			unsafe {
+				total += (*current).size;
+				current = (*current).next;
+			}
+		}
+		total
+	}
+}
+
+// ############################################################################
+// #                              GLOBAL ALLOCATOR                            #
+// ############################################################################
+
+/// The `#[global_allocator]`, backing `Box`, `Vec`, `String`, `BTreeMap`...
+///
+/// The heap is locked with interrupts disabled, so that an interrupt handler
+/// allocating memory cannot deadlock on a lock held by the code it interrupted.
+pub struct KernelHeap {
+	heap: spin::Mutex<Heap>,
+}
+
+impl KernelHeap {
+	const fn new() -> Self {
+		Self {
+			heap: spin::Mutex::new(Heap::new()),
+		}
+	}
+}
+
+unsafe impl GlobalAlloc for KernelHeap {
+	unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+		interrupts::without_interrupts(|| self.heap.lock().allocate(layout))
+	}
+
+	unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+		interrupts::without_interrupts(|| unsafe { self.heap.lock().deallocate(ptr, layout) })
+	}
+}
+
+/// Current break, bytes available in the free list.
+pub fn stats() -> (usize, usize) {
+	interrupts::without_interrupts(|| {
+		let heap = ALLOCATOR.heap.lock();
+		(heap.brk, heap.free_bytes())
+	})
+}
+
+/// Allocates every free region, so that the next allocation has to grow the
+/// heap. Returns the blocks (at most `N`) to give back with [`unfill`].
+#[cfg(test)]
+pub(super) fn fill<const N: usize>() -> ([(*mut u8, Layout); N], usize) {
+	let mut blocks = [(ptr::null_mut(), Layout::new::<u8>()); N];
+	let mut count: usize = 0;
+	loop {
+		let head_size = interrupts::without_interrupts(|| {
+			let heap = ALLOCATOR.heap.lock();
+			if heap.head.is_null() { 0 } else { unsafe { (*heap.head).size } }
+		});
+		if 0 == head_size {
+			return (blocks, count);
+		}
+		// First fit: takes the whole head region
+		let layout = Layout::from_size_align(head_size, NODE_ALIGN).unwrap();
+		let block = unsafe { ALLOCATOR.alloc(layout) };
+		assert!(!block.is_null());
+		*blocks.get_mut(count).expect("fill: too many free regions") = (block, layout);
+		count += 1;
+	}
+}
+
+/// ## Safety
+///
+/// `blocks` must have been returned by [`fill`] and not given back since.
+#[cfg(test)]
+pub(super) unsafe fn unfill(blocks: &[(*mut u8, Layout)]) -> () {
+	for &(block, layout) in blocks {
+		unsafe { ALLOCATOR.dealloc(block, layout) };
+	}
+}
+
+// ############################################################################
+// #                              KMALLOC                                     #
+// ############################################################################
+
+/// Size of the header holding the requested size in front of every `kmalloc` block.
+/// It also is the alignment of the returned pointers.
+const KMALLOC_HEADER_SIZE: usize = 8;
+
+fn kmalloc_layout(size: usize) -> Option<Layout> {
+	let total = size.checked_add(KMALLOC_HEADER_SIZE)?;
+	Layout::from_size_align(total, KMALLOC_HEADER_SIZE).ok()
+}
+
+/// Allocates `size` bytes on the kernel heap, returns null on failure.
+pub fn kmalloc(size: usize) -> *mut u8 {
+	let layout = match kmalloc_layout(size) {
+		Some(layout) => layout,
+		None => return ptr::null_mut(),
+	};
+	let block = unsafe { ALLOCATOR.alloc(layout) };
+	if block.is_null() {
+		return block;
+	}
+	unsafe {
+		(block as *mut usize).write(size);
+		block.add(KMALLOC_HEADER_SIZE)
+	}
+}
+
+/// Releases a block returned by [`kmalloc`]. Does nothing on null.
+///
+/// ## Safety
+///
+/// `ptr` must have been returned by `kmalloc` and not freed since.
+pub unsafe fn kfree(ptr: *mut u8) -> () {
+	if !ptr.is_null() {
+		unsafe {
+			let size = ksize(ptr);
+			ALLOCATOR.dealloc(ptr.sub(KMALLOC_HEADER_SIZE), kmalloc_layout(size).unwrap());
+		}
+	}
+}
+
+/// Returns the size requested for a block returned by [`kmalloc`].
+///
+/// ## Safety
+///
+/// `ptr` must have been returned by `kmalloc` and not freed since.
+pub unsafe fn ksize(ptr: *const u8) -> usize {
+	unsafe { (ptr.sub(KMALLOC_HEADER_SIZE) as *const usize).read() }
+}
+
+/// Moves the heap break `increment` bytes up (rounded up to a multiple of 8)
+/// and returns the previous break, or null if the heap cannot grow.
+///
+/// The new memory is added to the free list, so it is only meant to
+/// reserve room ahead of time: `kmalloc` grows the heap on its own.
+pub fn kbrk(increment: usize) -> *mut u8 {
+	let increment = match increment.checked_next_multiple_of(NODE_SIZE) {
+		Some(increment) => increment,
+		None => return ptr::null_mut(),
+	};
+	interrupts::without_interrupts(|| {
+		let mut heap = ALLOCATOR.heap.lock();
+		match heap.sbrk(increment) {
+			Some(old_brk) => {
+				if 0 < increment {
+					unsafe { heap.add_free_region(old_brk, increment) };
+				}
+				old_brk as *mut u8
+			},
+			None => ptr::null_mut(),
+		}
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use alloc::vec::Vec;
+	use super::{kfree, kmalloc, ksize};
+
+	#[test_case]
+	fn kmalloc_kfree() {
+		let ptr = kmalloc(100);
+		assert!(!ptr.is_null());
+		assert_eq!(100, unsafe { ksize(ptr) });
+		unsafe { kfree(ptr) };
+	}
+
+	#[test_case]
+	fn vec_grows_past_a_page() {
+		let numbers: Vec<usize> = (0..4096).collect();
+		assert_eq!(4096 * 4095 / 2, numbers.iter().sum::<usize>());
+	}
+}
@@ -0,0 +1,112 @@
+pub mod frame_allocator;
+pub mod heap;
+pub mod paging;
+pub mod vmalloc;
+
+use self::frame_allocator::BitmapFrameAllocator;
+use crate::arch::x86::structures::paging as paging_structures;
+use crate::gdt;
+use crate::multiboot2::BootInformation;
+
+// https://os.phil-opp.com/edition-1/allocating-frames/
+// https://wiki.osdev.org/Page_Frame_Allocation
+
+pub const PAGE_SIZE: usize = 4096;
+
+/// Physical memory above this address is ignored: it could not be
+/// identity-mapped without overlapping the kernel virtual areas.
+const PHYS_MEMORY_LIMIT: u64 = 0xc0000000;
+
+/// Virtual area of the kernel heap, mapped on demand (see `heap::kbrk`).
+pub const KERNEL_HEAP_START: usize = 0xd0000000;
+pub const KERNEL_HEAP_MAX_SIZE: usize = 0x10000000;
+
+/// Virtual area handed out by `vmalloc::vmalloc`.
+pub const VMALLOC_START: usize = 0xe0000000;
+pub const VMALLOC_END: usize = 0xf0000000;
+
+/// VGA memory-mapped I/O (8 screens, see `vga::VGA`).
+const VGA_ADDR: usize = 0x000b8000;
+const VGA_END_ADDR: usize = 0x000c0000;
+
+pub static FRAME_ALLOCATOR: spin::Mutex<BitmapFrameAllocator> = spin::Mutex::new(BitmapFrameAllocator::new());
+
+// Symbols defined in arch/x86/linker.ld and asm/src/boot.s
+extern "C" {
+	static kernel_start: u8;
+	static kernel_end: u8;
+	static stack_guard: u8;
+	static stack_bottom: u8;
+	static stack_top: u8;
+}
+
+// ===== Frame =====
+
+/// A 4 KiB physical memory frame.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Frame {
+	number: usize,
+}
+
+impl Frame {
+	/// Returns the frame that contains the given physical address.
+	pub fn containing_address(addr: usize) -> Self {
+		Self {
+			number: addr / PAGE_SIZE,
+		}
+	}
+
+	/// Physical address of the first byte of the frame.
+	pub fn start_address(&self) -> usize {
+		self.number * PAGE_SIZE
+	}
+
+	pub fn number(&self) -> usize {
+		self.number
+	}
+}
+
+// ===== FrameAllocator =====
+
+/// Something able to hand out and take back physical frames.
+pub trait FrameAllocator {
+	fn allocate_frame(&mut self) -> Option<Frame>;
+	fn deallocate_frame(&mut self, frame: Frame) -> ();
+}
+
+/// Physical memory ranges that must never be handed out, whatever the memory map says.
+fn reserved_areas(boot_info: &BootInformation) -> [(usize, usize); 5] {
+	[
+		(core::ptr::addr_of!(kernel_start) as usize, core::ptr::addr_of!(kernel_end) as usize),
+		(core::ptr::addr_of!(stack_bottom) as usize, core::ptr::addr_of!(stack_top) as usize),
+		(VGA_ADDR, VGA_END_ADDR),
+		(gdt::GDT_ADDR, gdt::GDT_ADDR + gdt::GDT_SIZE),
+		(boot_info.start_address(), boot_info.end_address()),
+	]
+}
+
+/// Builds the frame allocator from the usable areas of the Multiboot2 memory map,
+/// then enables paging.
+pub fn init(boot_info: &BootInformation) -> () {
+	let memory_map = boot_info.memory_map().expect("memory::init: no memory map tag");
+	let mut allocator = FRAME_ALLOCATOR.lock();
+	for area in memory_map.available_areas() {
+		allocator.free_area(area.start_address(), core::cmp::min(area.end_address(), PHYS_MEMORY_LIMIT));
+	}
+	for (start, end) in reserved_areas(boot_info) {
+		allocator.reserve_area(start, end);
+	}
+	for module in boot_info.modules() {
+		allocator.reserve_area(module.start_address(), module.end_address());
+	}
+	let identity_end = (allocator.total_frames() * PAGE_SIZE).next_multiple_of(paging_structures::HUGE_PAGE_SIZE);
+	paging::init(identity_end, &mut *allocator);
+
+	// Make a kernel stack overflow page fault (then double fault) instead of corrupting .bss.
+	// The frame stays reserved with the kernel image. If the image grows past the
+	// first 4 MiB, the guard page lies in a huge page and stays mapped.
+	let guard = paging_structures::Page::containing_address(core::ptr::addr_of!(stack_guard) as usize);
+	if let Some(mapper) = paging::MAPPER.lock().as_mut() {
+		let _ = mapper.unmap(guard);
+	}
+}
@@ -0,0 +1,54 @@
+use crate::arch::x86::registers::control::{Cr0, Cr0Flags, Cr3, Cr4, Cr4Flags};
+use crate::arch::x86::structures::paging::{Mapper, PageDirectory, PageTableFlags, HUGE_PAGE_SIZE};
+use super::{Frame, FrameAllocator, PAGE_SIZE};
+
+/// The kernel address space, available once [`init`] has run.
+pub static MAPPER: spin::Mutex<Option<Mapper>> = spin::Mutex::new(None);
+
+/// Builds the kernel page directory, then enables paging.
+///
+/// Physical memory is identity-mapped up to `identity_end`: the first 4 MiB
+/// (GDT on page 0, VGA, kernel image) with 4 KiB pages, the rest with 4 MiB
+/// pages. Page 0 stays mapped since the CPU reads descriptors from the GDT.
+/// Since page tables are reached through their physical address, every
+/// frame the allocator may hand out must be below `identity_end`.
+pub fn init<A: FrameAllocator>(identity_end: usize, allocator: &mut A) -> () {
+	let frame = allocator.allocate_frame().expect("paging::init: no frame left for the page directory");
+	let directory = frame.start_address() as *mut PageDirectory;
+	unsafe { (*directory).zero() };
+	let mut mapper = unsafe { Mapper::new(directory) };
+
+	let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE;
+	let small_end = core::cmp::min(identity_end, HUGE_PAGE_SIZE);
+	for addr in (0..small_end).step_by(PAGE_SIZE) {
+		mapper.identity_map(Frame::containing_address(addr), flags, allocator)
+			.expect("paging::init: identity mapping failed");
+	}
+	for addr in (HUGE_PAGE_SIZE..identity_end).step_by(HUGE_PAGE_SIZE) {
+		mapper.map_huge(addr, addr, flags)
+			.expect("paging::init: identity mapping failed");
+	}
+
+	unsafe {
+		Cr4::update(|flags| flags.insert(Cr4Flags::PAGE_SIZE_EXTENSION));
+		Cr3::write(mapper.directory_address() as u32);
+		Cr0::update(|flags| flags.insert(Cr0Flags::PAGING | Cr0Flags::WRITE_PROTECT));
+	}
+	*MAPPER.lock() = Some(mapper);
+}
+
+#[cfg(test)]
+mod tests {
+	use super::MAPPER;
+	use crate::arch::x86::instructions::interrupts;
+	use crate::gdt;
+
+	#[test_case]
+	fn gdt_page_is_identity_mapped() {
+		let translation = interrupts::without_interrupts(|| {
+			let mapper = MAPPER.lock();
+			mapper.as_ref().unwrap().translate(gdt::GDT_ADDR)
+		});
+		assert_eq!(Some(gdt::GDT_ADDR), translation);
+	}
+}
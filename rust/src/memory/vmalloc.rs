@@ -0,0 +1,208 @@
+use alloc::collections::BTreeMap;
+use core::ptr;
+use bitflags::bitflags;
+use crate::arch::x86::instructions::interrupts;
+use crate::arch::x86::structures::paging::{Mapper, Page, PageTableFlags};
+use super::{paging, FrameAllocator, FRAME_ALLOCATOR, PAGE_SIZE, VMALLOC_END, VMALLOC_START};
+
+// https://www.kernel.org/doc/gorman/html/understand/understand010.html
+
+bitflags! {
+	/// Access rights of the pages backing a `vmalloc` area.
+	#[repr(transparent)]
+	#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy)]
+	pub struct VmFlags: u32 {
+		const WRITABLE = 1;
+		const USER = 1 << 1;
+	}
+}
+
+impl From<VmFlags> for PageTableFlags {
+	fn from(flags: VmFlags) -> Self {
+		let mut page_flags = PageTableFlags::PRESENT;
+		if flags.contains(VmFlags::WRITABLE) {
+			page_flags |= PageTableFlags::WRITABLE;
+		}
+		if flags.contains(VmFlags::USER) {
+			page_flags |= PageTableFlags::USER_ACCESSIBLE;
+		}
+		page_flags
+	}
+}
+
+/// A virtually contiguous area, backed by frames that need not be contiguous.
+#[derive(Debug, Copy, Clone)]
+struct Area {
+	/// Size requested by the caller.
+	size: usize,
+	/// Number of mapped pages (the unmapped guard page is not counted).
+	pages: usize,
+	flags: VmFlags,
+}
+
+impl Area {
+	/// Pages the area occupies in the virtual range, guard page included.
+	fn span(&self) -> usize {
+		(self.pages + 1) * PAGE_SIZE
+	}
+}
+
+/// Areas handed out in `VMALLOC_START..VMALLOC_END`, by start address.
+///
+/// Each area is followed by an unmapped guard page, so that overflowing
+/// it page faults instead of silently corrupting the next one.
+static AREAS: spin::Mutex<BTreeMap<usize, Area>> = spin::Mutex::new(BTreeMap::new());
+
+/// Finds the lowest gap able to hold `span` bytes.
+fn find_gap(areas: &BTreeMap<usize, Area>, span: usize) -> Option<usize> {
+	let mut start = VMALLOC_START;
+	for (&addr, area) in areas.iter() {
+		if addr - start >= span {
+			return Some(start);
+		}
+		start = addr + area.span();
+	}
+	if VMALLOC_END - start >= span {
+		Some(start)
+	}
+	else {
+		None
+	}
+}
+
+/// Unmaps `pages` pages from `start` and gives their frames back.
+fn unmap_pages<A: FrameAllocator>(mapper: &mut Mapper, start: usize, pages: usize, allocator: &mut A) -> () {
+	for page in (0..pages).map(|i| Page::containing_address(start + i * PAGE_SIZE)) {
+		if let Ok(frame) = mapper.unmap(page) {
+			allocator.deallocate_frame(frame);
+		}
+	}
+}
+
+/// Maps the pages of `area` from `start` to fresh frames. On failure, unmaps
+/// the pages mapped so far and returns `false`.
+fn map_pages<A: FrameAllocator>(mapper: &mut Mapper, start: usize, area: &Area, allocator: &mut A) -> bool {
+	for i in 0..area.pages {
+		let page = Page::containing_address(start + i * PAGE_SIZE);
+		let mapped = match allocator.allocate_frame() {
+			Some(frame) => {
+				let result = mapper.map_to(page, frame, area.flags.into(), allocator);
+				if result.is_err() {
+					allocator.deallocate_frame(frame);
+				}
+				result.is_ok()
+			},
+			None => false,
+		};
+		if !mapped {
+			unmap_pages(mapper, start, i, allocator);
+			return false;
+		}
+	}
+	true
+}
+
+/// Allocates a virtually contiguous area of `size` bytes, returns null on failure.
+pub fn vmalloc(size: usize, flags: VmFlags) -> *mut u8 {
+	// Larger areas could not fit with their guard page, and their span would overflow
+	if 0 == size || size > VMALLOC_END - VMALLOC_START - PAGE_SIZE {
+		return ptr::null_mut();
+	}
+	let area = Area {
+		size,
+		pages: size.div_ceil(PAGE_SIZE),
+		flags,
+	};
+	interrupts::without_interrupts(|| {
+		let mut areas = AREAS.lock();
+		let start = match find_gap(&areas, area.span()) {
+			Some(start) => start,
+			None => return ptr::null_mut(),
+		};
+		// The paging locks are released before inserting in the map, which may
+		// grow the heap, and so take them again
+		let mapped = {
+			let mut mapper = paging::MAPPER.lock();
+			match mapper.as_mut() {
+				Some(mapper) => map_pages(mapper, start, &area, &mut *FRAME_ALLOCATOR.lock()),
+				None => false,
+			}
+		};
+		if !mapped {
+			return ptr::null_mut();
+		}
+		areas.insert(start, area);
+		start as *mut u8
+	})
+}
+
+/// Unmaps an area returned by [`vmalloc`] and gives its frames back.
+/// Does nothing on null.
+///
+/// ## Safety
+///
+/// `ptr` must have been returned by `vmalloc` and not freed since.
+pub unsafe fn vfree(ptr: *mut u8) -> () {
+	if ptr.is_null() {
+		return;
+	}
+	interrupts::without_interrupts(|| {
+		let area = match AREAS.lock().remove(&(ptr as usize)) {
+			Some(area) => area,
+			None => panic!("vfree: {:p} was not returned by vmalloc", ptr),
+		};
+		if let Some(mapper) = paging::MAPPER.lock().as_mut() {
+			unmap_pages(mapper, ptr as usize, area.pages, &mut *FRAME_ALLOCATOR.lock());
+		}
+	});
+}
+
+/// Returns the size requested for an area returned by [`vmalloc`], 0 for any other pointer.
+pub fn vsize(ptr: *const u8) -> usize {
+	interrupts::without_interrupts(|| {
+		AREAS.lock().get(&(ptr as usize)).map_or(0, |area| area.size)
+	})
+}
+
+/// Returns the flags an area returned by [`vmalloc`] was mapped with.
+pub fn vflags(ptr: *const u8) -> Option<VmFlags> {
+	interrupts::without_interrupts(|| {
+		AREAS.lock().get(&(ptr as usize)).map(|area| area.flags)
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{vfree, vmalloc, vsize, VmFlags};
+	use crate::memory::{heap, PAGE_SIZE, VMALLOC_END, VMALLOC_START};
+
+	#[test_case]
+	fn vmalloc_vfree() {
+		let ptr = vmalloc(2 * PAGE_SIZE + 1, VmFlags::WRITABLE);
+		assert!(!ptr.is_null());
+		assert_eq!(2 * PAGE_SIZE + 1, vsize(ptr));
+		unsafe {
+			ptr.add(2 * PAGE_SIZE).write(42);
+			vfree(ptr);
+		}
+		assert_eq!(0, vsize(ptr));
+	}
+
+	#[test_case]
+	fn vmalloc_too_large() {
+		assert!(vmalloc(usize::MAX, VmFlags::WRITABLE).is_null());
+		assert!(vmalloc(VMALLOC_END - VMALLOC_START, VmFlags::WRITABLE).is_null());
+	}
+
+	#[test_case]
+	fn vmalloc_on_a_full_heap() {
+		let (blocks, count) = heap::fill::<64>();
+		// Enough areas for the map of areas to allocate a node, growing the heap
+		let areas: [*mut u8; 12] = core::array::from_fn(|_| vmalloc(PAGE_SIZE, VmFlags::WRITABLE));
+		for ptr in areas {
+			assert!(!ptr.is_null());
+			unsafe { vfree(ptr) };
+		}
+		unsafe { heap::unfill(&blocks[..count]) };
+	}
+}
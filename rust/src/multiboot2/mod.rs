@@ -0,0 +1,646 @@
+use core::fmt::{self, Debug};
+use core::marker::PhantomData;
+
+// https://www.gnu.org/software/grub/manual/multiboot2/multiboot.html
+// - 3.3 I386 machine state
+// - 3.6 Boot information format
+// https://github.com/rust-osdev/multiboot2/tree/v0.1.0
+
+/// The value the bootloader leaves in EAX to tell the OS image it was loaded
+/// by a Multiboot2-compliant bootloader.
+pub const BOOTLOADER_MAGIC: u32 = 0x36d76289;
+
+/// Reasons for which the boot information structure could not be loaded.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LoadError {
+	/// EAX did not contain [`BOOTLOADER_MAGIC`].
+	InvalidMagic(u32),
+	/// The boot information pointer was null.
+	NullPointer,
+	/// The boot information pointer was not 8-byte aligned.
+	Misaligned(u32),
+	/// The tag list is not terminated by an end tag within `total_size` bytes.
+	MissingEndTag,
+}
+
+// ############################################################################
+// #                              TAG                                         #
+// ############################################################################
+
+/// The tag types defined by the specification (3.6.1 - 3.6.13).
+#[allow(dead_code)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u32)]
+pub enum TagType {
+	End = 0,
+	CommandLine = 1,
+	BootloaderName = 2,
+	Module = 3,
+	BasicMemoryInfo = 4,
+	BiosBootDevice = 5,
+	MemoryMap = 6,
+	Vbe = 7,
+	Framebuffer = 8,
+	ElfSections = 9,
+	Apm = 10,
+	Efi32 = 11,
+	Efi64 = 12,
+	Smbios = 13,
+	AcpiOld = 14,
+	AcpiNew = 15,
+	Network = 16,
+	EfiMemoryMap = 17,
+	EfiBootServicesNotTerminated = 18,
+	Efi32ImageHandle = 19,
+	Efi64ImageHandle = 20,
+	ImageLoadBaseAddr = 21,
+}
+
+/// The header shared by every tag: `u32 type; u32 size`.
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+pub struct Tag {
+	pub typ: u32,
+	pub size: u32,
+}
+
+impl Tag {
+	/// Address of the first byte following the tag header.
+	fn payload_addr(&self) -> usize {
+		self as *const _ as usize + core::mem::size_of::<Tag>()
+	}
+
+	/// Payload of the tag, that is everything after the 8 bytes header.
+	fn payload(&self) -> &[u8] {
+		let length = (self.size as usize).saturating_sub(core::mem::size_of::<Tag>());
+		unsafe { core::slice::from_raw_parts(self.payload_addr() as *const u8, length) }
+	}
+}
+
+/// Iterates over the tags of the boot information structure, stopping at the end tag.
+#[derive(Clone)]
+pub struct TagIter<'a> {
+	current: *const Tag,
+	end: usize,
+	phantom: PhantomData<&'a Tag>,
+}
+
+impl<'a> Iterator for TagIter<'a> {
+	type Item = &'a Tag;
+
+	fn next(&mut self) -> Option<&'a Tag> {
+		if (self.current as usize) + core::mem::size_of::<Tag>() > self.end {
+			return None;
+		}
+		let tag: &'a Tag = unsafe { &*self.current };
+		if TagType::End as u32 == tag.typ || (tag.size as usize) < core::mem::size_of::<Tag>() {
+			return None;
+		}
+		// Every tag starts at an 8-bytes aligned address
+		let next_addr = (self.current as usize + tag.size as usize + 7) & !7;
+		self.current = next_addr as *const Tag;
+		Some(tag)
+	}
+}
+
+/// Interprets a null-terminated string payload, dropping the terminator
+/// and anything that would not be valid UTF-8.
+fn payload_as_str(bytes: &[u8]) -> &str {
+	let length = bytes.iter().position(|&b| b'\0' == b).unwrap_or(bytes.len());
+	match core::str::from_utf8(&bytes[..length]) {
+		Ok(s) => s,
+		Err(e) => unsafe { core::str::from_utf8_unchecked(&bytes[..e.valid_up_to()]) },
+	}
+}
+
+// ############################################################################
+// #                              BOOT INFORMATION                            #
+// ############################################################################
+
+/// The fixed part of the boot information structure.
+#[repr(C)]
+struct BootInformationHeader {
+	total_size: u32,
+	_reserved: u32,
+}
+
+/// Read-only view over the boot information structure handed to us by the bootloader.
+///
+/// The structure lives wherever the bootloader put it: it must stay mapped
+/// (and must not be overwritten) for as long as this view is in use.
+pub struct BootInformation {
+	header: *const BootInformationHeader,
+}
+
+// The structure is never written to after boot
+unsafe impl Send for BootInformation {}
+unsafe impl Sync for BootInformation {}
+
+impl BootInformation {
+	/// Checks the magic value found in EAX and wraps the structure pointed to by EBX.
+	///
+	/// ## Safety
+	///
+	/// `addr` must be the physical address given by the bootloader, and that
+	/// memory must be identity-mapped (or paging disabled) while the returned value is used.
+	pub unsafe fn load(magic: u32, addr: u32) -> Result<Self, LoadError> {
+		if BOOTLOADER_MAGIC != magic {
+			return Err(LoadError::InvalidMagic(magic));
+		}
+		if 0 == addr {
+			return Err(LoadError::NullPointer);
+		}
+		if 0 != addr & 7 {
+			return Err(LoadError::Misaligned(addr));
+		}
+		let instance = Self {
+			header: addr as *const BootInformationHeader,
+		};
+		if !instance.has_valid_end_tag() {
+			return Err(LoadError::MissingEndTag);
+		}
+		Ok(instance)
+	}
+
+	fn has_valid_end_tag(&self) -> bool {
+		let mut tags = self.tags();
+		while tags.next().is_some() {}
+		if (tags.current as usize) + core::mem::size_of::<Tag>() > tags.end {
+			return false;
+		}
+		let end: &Tag = unsafe { &*tags.current };
+		TagType::End as u32 == end.typ && core::mem::size_of::<Tag>() as u32 == end.size
+	}
+
+	/// Physical address of the first byte of the structure.
+	pub fn start_address(&self) -> usize {
+		self.header as usize
+	}
+
+	/// Physical address of the first byte past the end of the structure.
+	pub fn end_address(&self) -> usize {
+		self.start_address() + self.total_size()
+	}
+
+	/// Size of the whole structure in bytes, including the end tag.
+	pub fn total_size(&self) -> usize {
+		unsafe { (*self.header).total_size as usize }
+	}
+
+	/// Iterates over every tag, whether or not its type is known.
+	pub fn tags(&self) -> TagIter<'_> {
+		TagIter {
+			current: (self.start_address() + core::mem::size_of::<BootInformationHeader>()) as *const Tag,
+			end: self.end_address(),
+			phantom: PhantomData,
+		}
+	}
+
+	fn get_tag(&self, typ: TagType) -> Option<&Tag> {
+		self.tags().find(|tag| typ as u32 == tag.typ)
+	}
+
+	/// The command line given to the kernel in the GRUB menu entry.
+	pub fn command_line(&self) -> Option<&str> {
+		self.get_tag(TagType::CommandLine).map(|tag| payload_as_str(tag.payload()))
+	}
+
+	/// The name of the bootloader (e.g. "GRUB 2.06").
+	pub fn bootloader_name(&self) -> Option<&str> {
+		self.get_tag(TagType::BootloaderName).map(|tag| payload_as_str(tag.payload()))
+	}
+
+	/// The memory map provided by the BIOS (through the bootloader).
+	pub fn memory_map(&self) -> Option<MemoryMapTag<'_>> {
+		self.get_tag(TagType::MemoryMap).map(|tag| MemoryMapTag { tag })
+	}
+
+	/// The section headers of the kernel ELF image.
+	pub fn elf_sections(&self) -> Option<ElfSectionsTag<'_>> {
+		self.get_tag(TagType::ElfSections).map(|tag| ElfSectionsTag { tag })
+	}
+
+	/// The modules loaded alongside the kernel (one tag per module).
+	pub fn modules(&self) -> impl Iterator<Item = ModuleTag<'_>> {
+		self.tags()
+			.filter(|tag| TagType::Module as u32 == tag.typ)
+			.map(|tag| ModuleTag { tag })
+	}
+
+	/// The framebuffer set up by the bootloader, if any.
+	pub fn framebuffer(&self) -> Option<FramebufferTag<'_>> {
+		self.get_tag(TagType::Framebuffer).map(|tag| FramebufferTag { tag })
+	}
+}
+
+impl Debug for BootInformation {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.debug_struct("BootInformation")
+			.field("start_address", &format_args!("{:#x}", self.start_address()))
+			.field("total_size", &self.total_size())
+			.field("command_line", &self.command_line())
+			.field("bootloader_name", &self.bootloader_name())
+			.field("memory_map", &self.memory_map())
+			.field("elf_sections", &self.elf_sections())
+			.field("modules", &self.modules().count())
+			.field("framebuffer", &self.framebuffer())
+			.finish()
+	}
+}
+
+// ############################################################################
+// #                              MEMORY MAP                                  #
+// ############################################################################
+
+/// Type of a memory area, as reported by the BIOS (3.6.8).
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MemoryAreaType {
+	/// RAM the kernel is free to use.
+	Available,
+	/// Usable memory holding ACPI information.
+	AcpiReclaimable,
+	/// Memory that must be preserved across hibernation.
+	ReservedHibernate,
+	/// Memory occupied by defective RAM modules.
+	Defective,
+	/// Any other value: the area must not be used.
+	Reserved(u32),
+}
+
+impl From<u32> for MemoryAreaType {
+	fn from(typ: u32) -> Self {
+		match typ {
+			1 => Self::Available,
+			3 => Self::AcpiReclaimable,
+			4 => Self::ReservedHibernate,
+			5 => Self::Defective,
+			_ => Self::Reserved(typ),
+		}
+	}
+}
+
+/// An entry of the memory map.
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct MemoryArea {
+	base_addr: u64,
+	length: u64,
+	typ: u32,
+	_reserved: u32,
+}
+
+impl MemoryArea {
+	/// Physical address of the first byte of the area.
+	pub fn start_address(&self) -> u64 {
+		self.base_addr
+	}
+
+	/// Physical address of the first byte past the end of the area.
+	pub fn end_address(&self) -> u64 {
+		self.base_addr + self.length
+	}
+
+	/// Size of the area in bytes.
+	pub fn size(&self) -> u64 {
+		self.length
+	}
+
+	pub fn typ(&self) -> MemoryAreaType {
+		MemoryAreaType::from(self.typ)
+	}
+}
+
+impl Debug for MemoryArea {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.debug_struct("MemoryArea")
+			.field("start_address", &format_args!("{:#x}", self.start_address()))
+			.field("end_address", &format_args!("{:#x}", self.end_address()))
+			.field("typ", &self.typ())
+			.finish()
+	}
+}
+
+/// Layout of the memory map tag payload, preceding the entries.
+#[repr(C)]
+struct MemoryMapHeader {
+	entry_size: u32,
+	entry_version: u32,
+}
+
+#[derive(Copy, Clone)]
+pub struct MemoryMapTag<'a> {
+	tag: &'a Tag,
+}
+
+impl<'a> MemoryMapTag<'a> {
+	fn header(&self) -> &'a MemoryMapHeader {
+		unsafe { &*(self.tag.payload_addr() as *const MemoryMapHeader) }
+	}
+
+	/// Iterates over every entry, whatever its type.
+	pub fn all_areas(&self) -> impl Iterator<Item = &'a MemoryArea> + Clone {
+		let entry_size = core::cmp::max(self.header().entry_size as usize, 1);
+		let first = self.tag.payload_addr() + core::mem::size_of::<MemoryMapHeader>();
+		let end = self.tag as *const _ as usize + self.tag.size as usize;
+		(first..end)
+			.step_by(entry_size)
+			.take_while(move |addr| addr + core::mem::size_of::<MemoryArea>() <= end)
+			.map(|addr| unsafe { &*(addr as *const MemoryArea) })
+	}
+
+	/// Iterates over the entries describing RAM available to the kernel.
+	pub fn available_areas(&self) -> impl Iterator<Item = &'a MemoryArea> + Clone {
+		self.all_areas().filter(|area| MemoryAreaType::Available == area.typ())
+	}
+}
+
+impl Debug for MemoryMapTag<'_> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.debug_list().entries(self.all_areas()).finish()
+	}
+}
+
+// ############################################################################
+// #                              ELF SECTIONS                                #
+// ############################################################################
+
+/// Layout of the ELF sections tag payload, preceding the section headers.
+#[repr(C)]
+struct ElfSectionsHeader {
+	num: u32,
+	entsize: u32,
+	shndx: u32,
+}
+
+/// An ELF32 section header (`Elf32_Shdr`).
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct ElfSection {
+	name: u32,
+	typ: u32,
+	flags: u32,
+	addr: u32,
+	offset: u32,
+	size: u32,
+	link: u32,
+	info: u32,
+	addralign: u32,
+	entsize: u32,
+}
+
+impl ElfSection {
+	/// `SHT_NULL`: the section header is inactive.
+	const TYPE_UNUSED: u32 = 0;
+	/// `SHF_WRITE`
+	pub const FLAG_WRITABLE: u32 = 0x1;
+	/// `SHF_ALLOC`
+	pub const FLAG_ALLOCATED: u32 = 0x2;
+	/// `SHF_EXECINSTR`
+	pub const FLAG_EXECUTABLE: u32 = 0x4;
+
+	pub fn start_address(&self) -> usize {
+		self.addr as usize
+	}
+
+	/// In 64 bits, like [`MemoryArea::end_address`], since a section may end
+	/// at the top of the address space.
+	pub fn end_address(&self) -> u64 {
+		self.addr as u64 + self.size as u64
+	}
+
+	pub fn size(&self) -> usize {
+		self.size as usize
+	}
+
+	pub fn typ(&self) -> u32 {
+		self.typ
+	}
+
+	pub fn flags(&self) -> u32 {
+		self.flags
+	}
+
+	/// Offset of the section name in the section header string table.
+	pub fn name_index(&self) -> u32 {
+		self.name
+	}
+
+	pub fn is_allocated(&self) -> bool {
+		0 != self.flags & Self::FLAG_ALLOCATED
+	}
+}
+
+impl Debug for ElfSection {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.debug_struct("ElfSection")
+			.field("addr", &format_args!("{:#x}", self.addr))
+			.field("size", &format_args!("{:#x}", self.size))
+			.field("typ", &self.typ)
+			.field("flags", &format_args!("{:#x}", self.flags))
+			.finish()
+	}
+}
+
+#[derive(Copy, Clone)]
+pub struct ElfSectionsTag<'a> {
+	tag: &'a Tag,
+}
+
+impl<'a> ElfSectionsTag<'a> {
+	fn header(&self) -> &'a ElfSectionsHeader {
+		unsafe { &*(self.tag.payload_addr() as *const ElfSectionsHeader) }
+	}
+
+	/// Index of the section holding the section names.
+	pub fn string_table_index(&self) -> usize {
+		self.header().shndx as usize
+	}
+
+	/// Iterates over the section headers, skipping the inactive ones.
+	pub fn sections(&self) -> impl Iterator<Item = &'a ElfSection> + Clone {
+		let header = self.header();
+		let entry_size = core::cmp::max(header.entsize as usize, 1);
+		let first = self.tag.payload_addr() + core::mem::size_of::<ElfSectionsHeader>();
+		(0..header.num as usize)
+			.map(move |i| unsafe { &*((first + i * entry_size) as *const ElfSection) })
+			.filter(|section| ElfSection::TYPE_UNUSED != section.typ)
+	}
+
+	/// Name of a section, looked up in the section header string table.
+	///
+	/// The string table must be loaded in memory (GRUB loads it).
+	pub fn section_name(&self, section: &ElfSection) -> Option<&'a str> {
+		let header = self.header();
+		if header.shndx >= header.num {
+			return None;
+		}
+		let entry_size = header.entsize as usize;
+		let first = self.tag.payload_addr() + core::mem::size_of::<ElfSectionsHeader>();
+		let strtab: &ElfSection = unsafe { &*((first + header.shndx as usize * entry_size) as *const ElfSection) };
+		if 0 == strtab.addr || section.name >= strtab.size {
+			return None;
+		}
+		let bytes = unsafe {
+			core::slice::from_raw_parts(
+				(strtab.addr + section.name) as *const u8,
+				(strtab.size - section.name) as usize,
+			)
+		};
+		Some(payload_as_str(bytes))
+	}
+}
+
+impl Debug for ElfSectionsTag<'_> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.debug_list().entries(self.sections()).finish()
+	}
+}
+
+// ############################################################################
+// #                              MODULES                                     #
+// ############################################################################
+
+/// Layout of the module tag payload, preceding the module string.
+#[repr(C)]
+struct ModuleHeader {
+	mod_start: u32,
+	mod_end: u32,
+}
+
+#[derive(Copy, Clone)]
+pub struct ModuleTag<'a> {
+	tag: &'a Tag,
+}
+
+impl<'a> ModuleTag<'a> {
+	fn header(&self) -> &'a ModuleHeader {
+		unsafe { &*(self.tag.payload_addr() as *const ModuleHeader) }
+	}
+
+	/// Physical address of the first byte of the module.
+	pub fn start_address(&self) -> usize {
+		self.header().mod_start as usize
+	}
+
+	/// Physical address of the first byte past the end of the module.
+	pub fn end_address(&self) -> usize {
+		self.header().mod_end as usize
+	}
+
+	/// The string following the module path in the GRUB menu entry.
+	pub fn command_line(&self) -> &'a str {
+		payload_as_str(self.tag.payload().get(core::mem::size_of::<ModuleHeader>()..).unwrap_or(&[]))
+	}
+}
+
+impl Debug for ModuleTag<'_> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.debug_struct("ModuleTag")
+			.field("start_address", &format_args!("{:#x}", self.start_address()))
+			.field("end_address", &format_args!("{:#x}", self.end_address()))
+			.field("command_line", &self.command_line())
+			.finish()
+	}
+}
+
+// ############################################################################
+// #                              FRAMEBUFFER                                 #
+// ############################################################################
+
+/// Layout of the framebuffer tag payload, preceding the color information.
+#[repr(C, packed)]
+struct FramebufferHeader {
+	addr: u64,
+	pitch: u32,
+	width: u32,
+	height: u32,
+	bpp: u8,
+	typ: u8,
+	_reserved: u16,
+}
+
+/// Position and size (in bits) of a color channel inside a pixel.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FramebufferField {
+	pub position: u8,
+	pub size: u8,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FramebufferType {
+	/// Pixels are indices into a palette of `palette_size` colors.
+	Indexed { palette_size: u16 },
+	/// Pixels directly encode their color.
+	Rgb { red: FramebufferField, green: FramebufferField, blue: FramebufferField },
+	/// EGA text mode: `width` and `height` are expressed in characters.
+	Text,
+	Unknown(u8),
+}
+
+#[derive(Copy, Clone)]
+pub struct FramebufferTag<'a> {
+	tag: &'a Tag,
+}
+
+impl<'a> FramebufferTag<'a> {
+	fn header(&self) -> &'a FramebufferHeader {
+		unsafe { &*(self.tag.payload_addr() as *const FramebufferHeader) }
+	}
+
+	/// Color information following the header.
+	fn color_info(&self) -> &'a [u8] {
+		self.tag.payload().get(core::mem::size_of::<FramebufferHeader>()..).unwrap_or(&[])
+	}
+
+	/// Physical address of the framebuffer.
+	pub fn address(&self) -> u64 {
+		self.header().addr
+	}
+
+	/// Number of bytes per row.
+	pub fn pitch(&self) -> u32 {
+		self.header().pitch
+	}
+
+	pub fn width(&self) -> u32 {
+		self.header().width
+	}
+
+	pub fn height(&self) -> u32 {
+		self.header().height
+	}
+
+	/// Number of bits per pixel.
+	pub fn bpp(&self) -> u8 {
+		self.header().bpp
+	}
+
+	pub fn typ(&self) -> FramebufferType {
+		let info = self.color_info();
+		match self.header().typ {
+			0 if 2 <= info.len() => FramebufferType::Indexed {
+				palette_size: u16::from_le_bytes([info[0], info[1]]),
+			},
+			1 if 6 <= info.len() => FramebufferType::Rgb {
+				red: FramebufferField { position: info[0], size: info[1] },
+				green: FramebufferField { position: info[2], size: info[3] },
+				blue: FramebufferField { position: info[4], size: info[5] },
+			},
+			2 => FramebufferType::Text,
+			typ => FramebufferType::Unknown(typ),
+		}
+	}
+}
+
+impl Debug for FramebufferTag<'_> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.debug_struct("FramebufferTag")
+			.field("address", &format_args!("{:#x}", self.address()))
+			.field("pitch", &self.pitch())
+			.field("width", &self.width())
+			.field("height", &self.height())
+			.field("bpp", &self.bpp())
+			.field("typ", &self.typ())
+			.finish()
+	}
+}
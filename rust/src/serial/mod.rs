@@ -0,0 +1,228 @@
+use core::fmt::Write;
+use core::sync::atomic::{AtomicU8, Ordering};
+use crate::arch::x86::instructions::interrupts;
+use crate::arch::x86::uart_16550::{InterruptEnable, SerialPort, COM1};
+use crate::interrupts::irq::{self, IrqReturn};
+use crate::keyboard;
+use crate::sync::ByteQueue;
+
+/// Baud rate COM1 is programmed with.
+const BAUD_RATE: u32 = 38400;
+
+lazy_static::lazy_static! {
+	/// COM1, or `None` if the UART did not pass its loopback test.
+	pub static ref _SERIAL: spin::Mutex<Option<SerialPort>> = {
+		let mut port = unsafe { SerialPort::new(COM1) };
+		spin::Mutex::new(port.init(BAUD_RATE).ok().map(|()| port))
+	};
+}
+
+// ===== Macros =====
+
+// https://os.phil-opp.com/testing/#serial-port
+
+#[macro_export]
+macro_rules! serial_print {
+	($($arg:tt)*) => {
+		$crate::serial::_print(core::format_args!($($arg)*))
+	};
+}
+
+#[macro_export]
+macro_rules! serial_println {
+	() => {
+		$crate::serial_print!("\n")
+	};
+	($($arg:tt)*) => {
+		$crate::serial_print!("{}\n", core::format_args!($($arg)*))
+	};
+}
+
+#[doc(hidden)]
+pub fn _print(args: core::fmt::Arguments) -> core::fmt::Result {
+	let mut result: core::fmt::Result = Err(core::fmt::Error);
+	interrupts::without_interrupts(|| {
+		if let Some(port) = _SERIAL.lock().as_mut() {
+			result = port.write_fmt(args);
+		}
+	});
+	result
+}
+
+// ===== Log sink =====
+
+/// Writes log records to COM1.
+pub struct SerialSink;
+
+impl crate::log::Sink for SerialSink {
+	fn write(&self, record: &crate::log::Record) -> () {
+		let _result: core::fmt::Result = crate::serial_println!("{}", record);
+	}
+}
+
+// ===== Input =====
+
+// https://en.wikipedia.org/wiki/ANSI_escape_code#CSI_(Control_Sequence_Introducer)_sequences
+
+/// Where we are in an escape sequence sent by the terminal.
+const ESCAPE_NONE: u8 = 0;
+const ESCAPE_ESC: u8 = 1;
+const ESCAPE_CSI: u8 = 2;
+
+static ESCAPE_STATE: AtomicU8 = AtomicU8::new(ESCAPE_NONE);
+
+// https://en.wikipedia.org/wiki/UTF-8#Encoding
+
+/// Reassembles the UTF-8 sequences terminals send for non-ASCII characters.
+struct Utf8Decoder {
+	code_point: u32,
+	/// Continuation bytes still expected.
+	remaining: u8,
+	/// Length of the sequence being decoded.
+	length: u8,
+}
+
+impl Utf8Decoder {
+	const fn new() -> Self {
+		Self {
+			code_point: 0,
+			remaining: 0,
+			length: 0,
+		}
+	}
+
+	/// Returns the character once its sequence is complete. Invalid and
+	/// interrupted sequences are dropped.
+	fn push(&mut self, byte: u8) -> Option<char> {
+		if byte.is_ascii() {
+			self.remaining = 0;
+			Some(byte as char)
+		}
+		else if 0x80 == byte & 0xC0 {
+			if 0 == self.remaining {
+				return None;
+			}
+			self.code_point = self.code_point << 6 | (byte & 0x3F) as u32;
+			self.remaining -= 1;
+			if 0 != self.remaining {
+				return None;
+			}
+			// Overlong encodings are rejected, surrogates by `from_u32`
+			char::from_u32(self.code_point).filter(|c| c.len_utf8() == self.length as usize)
+		}
+		else {
+			(self.length, self.code_point) = match byte {
+				0xC0..=0xDF => (2, (byte & 0x1F) as u32),
+				0xE0..=0xEF => (3, (byte & 0x0F) as u32),
+				0xF0..=0xF7 => (4, (byte & 0x07) as u32),
+				_ => (0, 0),
+			};
+			self.remaining = self.length.saturating_sub(1);
+			None
+		}
+	}
+}
+
+/// Only used outside interrupt context, see [`process_pending`].
+static UTF8: spin::Mutex<Utf8Decoder> = spin::Mutex::new(Utf8Decoder::new());
+
+/// Raises IRQ4 whenever COM1 receives a byte.
+pub fn init() -> () {
+	let present = interrupts::without_interrupts(|| {
+		_SERIAL.lock().as_mut().map(|port| port.set_interrupts(InterruptEnable::RECEIVED_DATA)).is_some()
+	});
+	if present {
+		if let Err(e) = irq::register(4, serial_irq) {
+			crate::error!("serial: cannot register IRQ4: {:?}", e);
+		}
+	}
+}
+
+/// Bytes received by the IRQ4 handler, waiting for [`process_pending`].
+static RECEIVED: ByteQueue<256> = ByteQueue::new();
+
+fn serial_irq() -> IrqReturn {
+	// The FIFO may hold several bytes, the UART keeps IRQ4 raised until it is drained
+	let mut handled = IrqReturn::None;
+	while let Some(byte) = receive() {
+		if !RECEIVED.push(byte) {
+			crate::warn!("serial: queue full, byte dropped");
+		}
+		handled = IrqReturn::Handled;
+	}
+	handled
+}
+
+/// Feeds the received bytes to the input path, outside interrupt context.
+pub fn process_pending() -> () {
+	while let Some(byte) = RECEIVED.pop() {
+		input(byte);
+	}
+}
+
+pub fn has_pending() -> bool {
+	!RECEIVED.is_empty()
+}
+
+/// Returns the next byte received on COM1, if any.
+pub fn receive() -> Option<u8> {
+	interrupts::without_interrupts(|| {
+		_SERIAL.lock().as_mut().and_then(|port| port.receive())
+	})
+}
+
+/// Translates a byte sent by the terminal into the keyboard input path.
+pub fn input(byte: u8) -> () {
+	match (ESCAPE_STATE.load(Ordering::Relaxed), byte) {
+		(ESCAPE_NONE, b'\x1b') => ESCAPE_STATE.store(ESCAPE_ESC, Ordering::Relaxed),
+		(ESCAPE_NONE, b'\r') => keyboard::input('\n'),
+		(ESCAPE_NONE, b'\x7f') => keyboard::input('\x08'), // Terminals send DEL for backspace
+		(ESCAPE_NONE, _) => {
+			let decoded = UTF8.lock().push(byte);
+			if let Some(c) = decoded {
+				keyboard::input(c);
+			}
+		},
+		(ESCAPE_ESC, b'[') => ESCAPE_STATE.store(ESCAPE_CSI, Ordering::Relaxed),
+		(ESCAPE_CSI, b'0'..=b'9' | b';') => {}, // Parameters are ignored
+		(ESCAPE_CSI, _) => {
+			match byte {
+				b'A' => keyboard::input('\x18'), // arrow up
+				b'B' => keyboard::input('\x19'), // arrow down
+				b'C' => keyboard::input('\x1a'), // arrow right
+				b'D' => keyboard::input('\x1b'), // arrow left
+				_ => {},
+			}
+			ESCAPE_STATE.store(ESCAPE_NONE, Ordering::Relaxed);
+		},
+		// A lone Esc, or Alt+key sent as Esc then the key: the byte is not
+		// part of a sequence
+		_ => {
+			ESCAPE_STATE.store(ESCAPE_NONE, Ordering::Relaxed);
+			input(byte);
+		},
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use alloc::vec::Vec;
+	use super::*;
+
+	#[test_case]
+	fn utf8_sequences_are_decoded() {
+		let mut decoder = Utf8Decoder::new();
+		let mut decode = |bytes: &[u8]| -> Vec<char> {
+			bytes.iter().filter_map(|&byte| decoder.push(byte)).collect()
+		};
+		assert_eq!(['a'], *decode(b"a"));
+		assert_eq!(['é'], *decode("é".as_bytes()));
+		assert_eq!(['€'], *decode("€".as_bytes()));
+		assert_eq!(['🦀'], *decode("🦀".as_bytes()));
+		assert_eq!(['x', 'é', 'y'], *decode("xéy".as_bytes()));
+		// Overlong '/', a surrogate, then a sequence cut by an ASCII byte
+		assert!(decode(&[0xC0, 0xAF]).is_empty());
+		assert!(decode(&[0xED, 0xA0, 0x80]).is_empty());
+		assert_eq!(['a'], *decode(&[0xC3, b'a', 0xA9]));
+	}
+}
@@ -0,0 +1,124 @@
+mod queue;
+
+pub use queue::ByteQueue;
+
+use core::mem::ManuallyDrop;
+use core::ops::{Deref, DerefMut};
+use crate::arch::x86::instructions::interrupts;
+
+// https://os.phil-opp.com/hardware-interrupts/#deadlocks
+// https://www.kernel.org/doc/html/latest/locking/spinlocks.html
+
+/// A spinlock that disables interrupts while it is held.
+///
+/// An interrupt handler taking a lock held by the code it interrupted would
+/// spin forever: with this lock, no interrupt can come while it is held.
+pub struct IrqSpinlock<T: ?Sized> {
+	inner: spin::Mutex<T>,
+}
+
+/// Releases the lock, then restores the interrupt flag, when dropped.
+///
+/// Guards must be dropped in the reverse order they were taken, or interrupts
+/// may be enabled again while an inner lock is still held.
+pub struct IrqSpinlockGuard<'a, T: ?Sized> {
+	guard: ManuallyDrop<spin::MutexGuard<'a, T>>,
+	/// Whether interrupts were enabled when the lock was taken.
+	interrupts_enabled: bool,
+}
+
+impl<T> IrqSpinlock<T> {
+	pub const fn new(value: T) -> Self {
+		Self {
+			inner: spin::Mutex::new(value),
+		}
+	}
+}
+
+impl<T: ?Sized> IrqSpinlock<T> {
+	/// Disables interrupts, then spins until the lock is available.
+	pub fn lock(&self) -> IrqSpinlockGuard<'_, T> {
+		let interrupts_enabled = interrupts::are_enabled();
+		interrupts::disable();
+		IrqSpinlockGuard {
+			guard: ManuallyDrop::new(self.inner.lock()),
+			interrupts_enabled,
+		}
+	}
+
+	/// Takes the lock if it is available, leaving interrupts as they were otherwise.
+	pub fn try_lock(&self) -> Option<IrqSpinlockGuard<'_, T>> {
+		let interrupts_enabled = interrupts::are_enabled();
+		interrupts::disable();
+		match self.inner.try_lock() {
+			Some(guard) => Some(IrqSpinlockGuard {
+				guard: ManuallyDrop::new(guard),
+				interrupts_enabled,
+			}),
+			None => {
+				if interrupts_enabled {
+					interrupts::enable();
+				}
+				None
+			},
+		}
+	}
+
+	pub fn is_locked(&self) -> bool {
+		self.inner.is_locked()
+	}
+}
+
+impl<T: ?Sized> Deref for IrqSpinlockGuard<'_, T> {
+	type Target = T;
+
+	fn deref(&self) -> &T {
+		&self.guard
+	}
+}
+
+impl<T: ?Sized> DerefMut for IrqSpinlockGuard<'_, T> {
+	fn deref_mut(&mut self) -> &mut T {
+		&mut self.guard
+	}
+}
+
+impl<T: ?Sized> Drop for IrqSpinlockGuard<'_, T> {
+	fn drop(&mut self) {
+		unsafe { ManuallyDrop::drop(&mut self.guard) };
+		if self.interrupts_enabled {
+			interrupts::enable();
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test_case]
+	fn guard_disables_and_restores_interrupts() {
+		let lock = IrqSpinlock::new(0);
+		assert!(interrupts::are_enabled());
+		{
+			let mut guard = lock.lock();
+			*guard += 1;
+			assert!(!interrupts::are_enabled());
+			assert!(lock.try_lock().is_none());
+			assert!(!interrupts::are_enabled());
+		}
+		assert!(interrupts::are_enabled());
+		assert_eq!(1, *lock.try_lock().unwrap());
+	}
+
+	#[test_case]
+	fn nested_guards_keep_interrupts_disabled() {
+		let outer = IrqSpinlock::new(());
+		let inner = IrqSpinlock::new(());
+		let outer_guard = outer.lock();
+		drop(inner.lock());
+		assert!(!interrupts::are_enabled());
+		drop(outer_guard);
+		assert!(interrupts::are_enabled());
+	}
+}
@@ -0,0 +1,77 @@
+use core::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+
+// https://www.kernel.org/doc/html/latest/core-api/circular-buffers.html
+
+/// A lock-free byte queue, for a single producer (typically an interrupt
+/// handler) and a single consumer.
+///
+/// `N` must be a power of two: indexes are free-running counters, which
+/// wrap around `usize` in step with the buffer.
+pub struct ByteQueue<const N: usize> {
+	buffer: [AtomicU8; N],
+	/// Bytes popped since creation, only written by the consumer.
+	head: AtomicUsize,
+	/// Bytes pushed since creation, only written by the producer.
+	tail: AtomicUsize,
+}
+
+impl<const N: usize> ByteQueue<N> {
+	const POWER_OF_TWO: () = assert!(N.is_power_of_two(), "ByteQueue: the capacity must be a power of two");
+
+	pub const fn new() -> Self {
+		#[allow(clippy::let_unit_value)]
+		let () = Self::POWER_OF_TWO;
+		Self {
+			buffer: [const { AtomicU8::new(0) }; N],
+			head: AtomicUsize::new(0),
+			tail: AtomicUsize::new(0),
+		}
+	}
+
+	/// Appends a byte, or returns `false` if the queue is full. Producer only.
+	pub fn push(&self, byte: u8) -> bool {
+		let tail = self.tail.load(Ordering::Relaxed);
+		if N == tail.wrapping_sub(self.head.load(Ordering::Acquire)) {
+			return false;
+		}
+		self.buffer[tail % N].store(byte, Ordering::Relaxed);
+		// Publishes the byte
+		self.tail.store(tail.wrapping_add(1), Ordering::Release);
+		true
+	}
+
+	/// Removes the oldest byte. Consumer only.
+	pub fn pop(&self) -> Option<u8> {
+		let head = self.head.load(Ordering::Relaxed);
+		if head == self.tail.load(Ordering::Acquire) {
+			return None;
+		}
+		let byte = self.buffer[head % N].load(Ordering::Relaxed);
+		// Hands the slot back to the producer
+		self.head.store(head.wrapping_add(1), Ordering::Release);
+		Some(byte)
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.head.load(Ordering::Relaxed) == self.tail.load(Ordering::Relaxed)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test_case]
+	fn fifo_order_and_overflow() {
+		let queue: ByteQueue<4> = ByteQueue::new();
+		assert_eq!(None, queue.pop());
+		for byte in 0..4 {
+			assert!(queue.push(byte));
+		}
+		assert!(!queue.push(4));
+		assert_eq!(Some(0), queue.pop());
+		assert!(queue.push(5));
+		assert_eq!([Some(1), Some(2), Some(3), Some(5), None], core::array::from_fn(|_| queue.pop()));
+		assert!(queue.is_empty());
+	}
+}
@@ -0,0 +1,61 @@
+use core::fmt;
+use crate::arch::x86::instructions::port::Port;
+use crate::{serial_print, serial_println};
+
+// https://os.phil-opp.com/testing/
+
+/// I/O port of the `isa-debug-exit` device (see the `test` rule of the Makefile).
+const ISA_DEBUG_EXIT_PORT: u16 = 0xf4;
+
+/// Written to `isa-debug-exit`, QEMU then exits with the status `(code << 1) | 1`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u32)]
+pub enum QemuExitCode {
+	/// QEMU exit status 33
+	Success = 0x10,
+	/// QEMU exit status 35
+	Failed = 0x11,
+}
+
+/// Exits QEMU, returns only if there is no `isa-debug-exit` device.
+pub fn exit_qemu(exit_code: QemuExitCode) -> () {
+	let mut port: Port<u32> = Port::new(ISA_DEBUG_EXIT_PORT);
+	unsafe {
+		port.write(exit_code as u32);
+	}
+}
+
+/// Something `#[test_case]` can be applied to.
+pub trait Testable {
+	fn run(&self) -> ();
+}
+
+impl<T: Fn()> Testable for T {
+	fn run(&self) -> () {
+		let _result: fmt::Result = serial_print!("{}... ", core::any::type_name::<T>());
+		self();
+		let _result: fmt::Result = serial_println!("[ok]");
+	}
+}
+
+/// Runs every `#[test_case]`, then exits QEMU.
+pub fn test_runner(tests: &[&dyn Testable]) -> ! {
+	let _result: fmt::Result = serial_println!("Running {} tests", tests.len());
+	for test in tests {
+		test.run();
+	}
+	exit_qemu(QemuExitCode::Success);
+	crate::hlt_loop();
+}
+
+/// Reports the running test as failed, then exits QEMU.
+pub fn panic_handler(info: &core::panic::PanicInfo) -> ! {
+	let _result: fmt::Result = serial_println!("[failed]\n\n{}", info);
+	exit_qemu(QemuExitCode::Failed);
+	crate::hlt_loop();
+}
+
+#[test_case]
+fn trivial_assertion() {
+	assert_eq!(1, 1);
+}
@@ -0,0 +1,64 @@
+use core::time::Duration;
+use crate::arch::x86::instructions::interrupts;
+
+// https://www.kernel.org/doc/html/latest/timers/timekeeping.html
+
+/// A monotonic counter the kernel can read the time from.
+pub trait ClockSource: Sync {
+	fn name(&self) -> &'static str;
+
+	/// The higher, the better (resolution, cost of a read, stability).
+	fn rating(&self) -> u32;
+
+	/// Time elapsed since boot.
+	fn read(&self) -> Duration;
+}
+
+/// The PIT tick count, always available but with a 1 tick resolution.
+pub struct PitClock;
+
+impl ClockSource for PitClock {
+	fn name(&self) -> &'static str {
+		"pit"
+	}
+
+	fn rating(&self) -> u32 {
+		100
+	}
+
+	fn read(&self) -> Duration {
+		super::uptime()
+	}
+}
+
+static PIT_CLOCK: PitClock = PitClock;
+
+static CURRENT: spin::Mutex<&'static dyn ClockSource> = spin::Mutex::new(&PIT_CLOCK);
+
+/// Makes `source` the clock source if it rates better than the current one.
+/// Returns whether it was selected.
+pub fn register(source: &'static dyn ClockSource) -> bool {
+	let selected = interrupts::without_interrupts(|| {
+		let mut current = CURRENT.lock();
+		if current.rating() < source.rating() {
+			*current = source;
+			true
+		}
+		else {
+			false
+		}
+	});
+	if selected {
+		crate::info!("clocksource: switched to {} (rating {})", source.name(), source.rating());
+	}
+	selected
+}
+
+pub fn current() -> &'static dyn ClockSource {
+	interrupts::without_interrupts(|| *CURRENT.lock())
+}
+
+/// Time elapsed since boot, read from the best clock source available.
+pub fn monotonic() -> Duration {
+	current().read()
+}
@@ -0,0 +1,111 @@
+use core::fmt;
+use crate::arch::x86::rtc_mc146818::RtcTime;
+
+// http://howardhinnant.github.io/date_algorithms.html
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// A date and time in UTC, from 1970 on.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DateTime {
+	pub year: u16,
+	/// From 1 to 12.
+	pub month: u8,
+	/// From 1 to 31.
+	pub day: u8,
+	pub hour: u8,
+	pub minute: u8,
+	pub second: u8,
+}
+
+impl DateTime {
+	/// The RTC only keeps the year of the century: years 70 to 99 are taken as
+	/// 1970 to 1999, the others as 2000 to 2069.
+	pub fn from_rtc(time: RtcTime) -> Self {
+		Self {
+			year: if 70 <= time.year { 1900 } else { 2000 } + time.year as u16,
+			month: time.month,
+			day: time.day,
+			hour: time.hour,
+			minute: time.minute,
+			second: time.second,
+		}
+	}
+
+	/// Seconds since 1970-01-01 00:00:00 UTC.
+	pub fn unix_timestamp(&self) -> u64 {
+		let days = days_from_civil(self.year as u64, self.month as u64, self.day as u64);
+		days * SECONDS_PER_DAY + self.hour as u64 * 3600 + self.minute as u64 * 60 + self.second as u64
+	}
+
+	pub fn from_unix_timestamp(timestamp: u64) -> Self {
+		let (year, month, day) = civil_from_days(timestamp / SECONDS_PER_DAY);
+		let seconds = timestamp % SECONDS_PER_DAY;
+		Self {
+			year: year as u16,
+			month: month as u8,
+			day: day as u8,
+			hour: (seconds / 3600) as u8,
+			minute: (seconds / 60 % 60) as u8,
+			second: (seconds % 60) as u8,
+		}
+	}
+}
+
+/// ISO 8601, e.g. `2026-10-18 12:30:59`.
+impl fmt::Display for DateTime {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(
+			f,
+			"{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+			self.year, self.month, self.day, self.hour, self.minute, self.second
+		)
+	}
+}
+
+/// Days since 1970-01-01, for a date from 1970 on.
+///
+/// Years are counted from March, so that the leap day ends them.
+fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+	let year = if month <= 2 { year - 1 } else { year };
+	let era = year / 400;
+	let year_of_era = year - era * 400;
+	let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+	let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+	era * 146097 + day_of_era - 719468
+}
+
+/// Inverse of [`days_from_civil`].
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+	let days = days + 719468;
+	let era = days / 146097;
+	let day_of_era = days - era * 146097;
+	let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+	let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+	let mp = (5 * day_of_year + 2) / 153;
+	let day = day_of_year - (153 * mp + 2) / 5 + 1;
+	let month = (mp + 2) % 12 + 1;
+	let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+	(year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test_case]
+	fn unix_timestamp() {
+		assert_eq!(0, DateTime::from_unix_timestamp(0).unix_timestamp());
+		let leap_day = DateTime { year: 2024, month: 2, day: 29, hour: 23, minute: 59, second: 59 };
+		assert_eq!(1709251199, leap_day.unix_timestamp());
+		assert_eq!(leap_day, DateTime::from_unix_timestamp(1709251199));
+		assert_eq!(DateTime { year: 2024, month: 3, day: 1, hour: 0, minute: 0, second: 0 }, DateTime::from_unix_timestamp(1709251200));
+	}
+
+	#[test_case]
+	fn from_rtc_century() {
+		let time = RtcTime { second: 0, minute: 0, hour: 0, day: 1, month: 1, year: 99 };
+		assert_eq!(1999, DateTime::from_rtc(time).year);
+		assert_eq!(2026, DateTime::from_rtc(RtcTime { year: 26, ..time }).year);
+	}
+}
@@ -0,0 +1,222 @@
+pub mod clocksource;
+mod datetime;
+pub mod timer;
+pub mod tsc;
+
+pub use datetime::DateTime;
+
+use core::ops::{Add, Sub};
+use core::sync::atomic::{AtomicU32, Ordering};
+use core::time::Duration;
+use crate::arch::x86::instructions::{self, interrupts};
+use crate::arch::x86::pit_8253::{self, Pit};
+use crate::arch::x86::rtc_mc146818::Rtc;
+use crate::interrupts::irq::{self, IrqReturn};
+
+/// Rate IRQ0 is raised at, in Hz.
+pub const TIMER_FREQUENCY: u32 = 1000;
+
+static _PIT: spin::Mutex<Pit> = spin::Mutex::new(unsafe { Pit::new() });
+
+static _RTC: spin::Mutex<Rtc> = spin::Mutex::new(unsafe { Rtc::new() });
+
+/// Programs the PIT to tick `frequency` times per second, calibrates the TSC,
+/// and reads the wall-clock time from the RTC.
+pub fn init(frequency: u32) -> () {
+	interrupts::without_interrupts(|| {
+		let mut pit = _PIT.lock();
+		if let Err(e) = pit.set_frequency(frequency) {
+			panic!("time::init: cannot program the PIT: {:?}", e);
+		}
+		DIVISOR.store(pit.divisor(), Ordering::Relaxed);
+	});
+	if let Err(e) = irq::register(0, timer_irq) {
+		panic!("time::init: cannot register the timer interrupt: {:?}", e);
+	}
+	tsc::init();
+	sync_rtc();
+	crate::info!("time: {} Hz timer, {} UTC", frequency, now());
+}
+
+// ===== Ticks =====
+
+/// A 64-bit counter, on a CPU that only has 32-bit atomics.
+///
+/// The timer interrupt is the only writer, and it cannot be interrupted by a
+/// reader: readers only have to retry if it ran between their two loads.
+struct TickCounter {
+	low: AtomicU32,
+	high: AtomicU32,
+}
+
+impl TickCounter {
+	const fn new() -> Self {
+		Self {
+			low: AtomicU32::new(0),
+			high: AtomicU32::new(0),
+		}
+	}
+
+	/// Only called by the timer interrupt handler.
+	fn increment(&self) -> () {
+		let low = self.low.load(Ordering::Relaxed).wrapping_add(1);
+		self.low.store(low, Ordering::Relaxed);
+		if 0 == low {
+			self.high.fetch_add(1, Ordering::Release);
+		}
+	}
+
+	fn load(&self) -> u64 {
+		loop {
+			let high = self.high.load(Ordering::Acquire);
+			let low = self.low.load(Ordering::Relaxed);
+			if high == self.high.load(Ordering::Acquire) {
+				return (high as u64) << 32 | low as u64;
+			}
+		}
+	}
+}
+
+static TICKS: TickCounter = TickCounter::new();
+
+/// PIT oscillator periods per tick, the BIOS default until [`init`] runs.
+static DIVISOR: AtomicU32 = AtomicU32::new(0x10000);
+
+fn timer_irq() -> IrqReturn {
+	TICKS.increment();
+	timer::run_expired(ticks());
+	IrqReturn::Handled
+}
+
+/// Timer interrupts received since boot.
+pub fn ticks() -> u64 {
+	TICKS.load()
+}
+
+/// Converts a number of ticks to a duration, at the rate the PIT is programmed with.
+fn ticks_to_duration(ticks: u64) -> Duration {
+	let periods = ticks * DIVISOR.load(Ordering::Relaxed) as u64;
+	let base = pit_8253::BASE_FREQUENCY as u64;
+	Duration::new(periods / base, ((periods % base) * 1_000_000_000 / base) as u32)
+}
+
+/// Converts a duration to a number of ticks, rounded up.
+pub fn duration_to_ticks(duration: Duration) -> u64 {
+	let period_nanos = DIVISOR.load(Ordering::Relaxed) as u128 * 1_000_000_000;
+	let periods = duration.as_nanos() * pit_8253::BASE_FREQUENCY as u128;
+	periods.div_ceil(period_nanos) as u64
+}
+
+/// Time elapsed since the PIT was programmed, with a resolution of one tick.
+pub fn uptime() -> Duration {
+	ticks_to_duration(ticks())
+}
+
+// ===== Wall clock =====
+
+/// Unix timestamp of the RTC when [`sync_rtc`] last ran, minus the uptime at
+/// that point: adding the uptime gives the current time.
+static BOOT_TIME: AtomicU32 = AtomicU32::new(0);
+
+/// Reads the date and time from the RTC.
+pub fn read_rtc() -> DateTime {
+	DateTime::from_rtc(interrupts::without_interrupts(|| _RTC.lock().read()))
+}
+
+/// Sets the wall clock from the RTC, which only has a resolution of a second.
+pub fn sync_rtc() -> () {
+	let timestamp = read_rtc().unix_timestamp();
+	BOOT_TIME.store((timestamp - uptime().as_secs()) as u32, Ordering::Relaxed);
+}
+
+/// Time elapsed since 1970-01-01 00:00:00 UTC.
+pub fn unix_time() -> Duration {
+	Duration::from_secs(BOOT_TIME.load(Ordering::Relaxed) as u64) + uptime()
+}
+
+/// Current date and time, in UTC.
+pub fn now() -> DateTime {
+	DateTime::from_unix_timestamp(unix_time().as_secs())
+}
+
+// ===== Instant =====
+
+/// A point in time, measured by the monotonic clock (see [`clocksource`]).
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Instant(Duration);
+
+impl Instant {
+	pub fn now() -> Self {
+		Self(clocksource::monotonic())
+	}
+
+	/// Time elapsed since boot at this instant.
+	pub fn since_boot(&self) -> Duration {
+		self.0
+	}
+
+	/// Returns zero if `earlier` is later than `self`.
+	pub fn duration_since(&self, earlier: Self) -> Duration {
+		self.0.saturating_sub(earlier.0)
+	}
+
+	pub fn elapsed(&self) -> Duration {
+		Self::now().duration_since(*self)
+	}
+
+	pub fn checked_add(&self, duration: Duration) -> Option<Self> {
+		self.0.checked_add(duration).map(Self)
+	}
+}
+
+impl Add<Duration> for Instant {
+	type Output = Self;
+
+	fn add(self, rhs: Duration) -> Self {
+		Self(self.0 + rhs)
+	}
+}
+
+impl Sub for Instant {
+	type Output = Duration;
+
+	fn sub(self, rhs: Self) -> Duration {
+		self.duration_since(rhs)
+	}
+}
+
+// ===== Sleep =====
+
+/// Halts the CPU until `duration` has elapsed, waking up on every interrupt.
+///
+/// Interrupts must be enabled, otherwise the tick count never moves.
+pub fn sleep(duration: Duration) -> () {
+	assert!(interrupts::are_enabled(), "time::sleep: interrupts are disabled");
+	let deadline = Instant::now() + duration;
+	while Instant::now() < deadline {
+		instructions::hlt();
+	}
+}
+
+pub fn sleep_ms(ms: u64) -> () {
+	sleep(Duration::from_millis(ms));
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test_case]
+	fn ticks_to_duration_uses_the_programmed_divisor() {
+		// 1000 Hz is programmed as 1193 oscillator periods per tick, a bit faster
+		assert_eq!(1193, DIVISOR.load(Ordering::Relaxed));
+		assert_eq!(Duration::from_nanos(999_847_466), ticks_to_duration(1000));
+	}
+
+	#[test_case]
+	fn sleep_ms_waits() {
+		let start = Instant::now();
+		sleep_ms(20);
+		assert!(Duration::from_millis(20) <= start.elapsed());
+	}
+}
@@ -0,0 +1,196 @@
+use alloc::boxed::Box;
+use alloc::collections::BinaryHeap;
+use core::cmp::Ordering;
+use core::time::Duration;
+use crate::arch::x86::instructions::interrupts;
+
+// https://www.kernel.org/doc/html/latest/timers/timers-howto.html
+
+/// Identifies a registered timer, see [`cancel`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TimerId(u64);
+
+type Callback = Box<dyn FnMut() + Send>;
+
+struct Timer {
+	/// Tick count from which the callback runs.
+	deadline: u64,
+	id: TimerId,
+	/// Ticks between two runs of a periodic timer.
+	period: Option<u64>,
+	callback: Callback,
+}
+
+// The earliest deadline comes first in the (max-)heap, ties are broken by
+// registration order
+impl Ord for Timer {
+	fn cmp(&self, other: &Self) -> Ordering {
+		(other.deadline, other.id).cmp(&(self.deadline, self.id))
+	}
+}
+
+impl PartialOrd for Timer {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+impl PartialEq for Timer {
+	fn eq(&self, other: &Self) -> bool {
+		self.id == other.id
+	}
+}
+
+impl Eq for Timer {}
+
+struct TimerQueue {
+	timers: BinaryHeap<Timer>,
+	next_id: u64,
+	/// Timer whose callback is running, out of the heap meanwhile.
+	running: Option<TimerId>,
+	/// Whether the running timer was cancelled by its callback.
+	running_cancelled: bool,
+}
+
+static TIMERS: spin::Mutex<TimerQueue> = spin::Mutex::new(TimerQueue {
+	timers: BinaryHeap::new(),
+	next_id: 0,
+	running: None,
+	running_cancelled: false,
+});
+
+fn add(deadline: u64, period: Option<u64>, callback: Callback) -> TimerId {
+	interrupts::without_interrupts(|| {
+		let mut queue = TIMERS.lock();
+		let id = TimerId(queue.next_id);
+		queue.next_id += 1;
+		queue.timers.push(Timer { deadline, id, period, callback });
+		id
+	})
+}
+
+/// Runs `callback` once, at the first tick from `deadline` on.
+///
+/// Callbacks run in the timer interrupt handler, with interrupts disabled:
+/// they must be short, and must not wait for a lock that may be held by
+/// the code they interrupted.
+pub fn add_oneshot<F>(deadline: u64, callback: F) -> TimerId
+where
+	F: FnMut() + Send + 'static,
+{
+	add(deadline, None, Box::new(callback))
+}
+
+/// Runs `callback` at `deadline`, then every `period` ticks.
+///
+/// See [`add_oneshot`] for the context callbacks run in.
+pub fn add_periodic<F>(deadline: u64, period: u64, callback: F) -> TimerId
+where
+	F: FnMut() + Send + 'static,
+{
+	assert!(0 < period, "timer::add_periodic: the period must not be zero");
+	add(deadline, Some(period), Box::new(callback))
+}
+
+/// Runs `callback` once, after `delay`.
+pub fn after<F>(delay: Duration, callback: F) -> TimerId
+where
+	F: FnMut() + Send + 'static,
+{
+	add_oneshot(super::ticks() + super::duration_to_ticks(delay), callback)
+}
+
+/// Runs `callback` every `period`, starting one period from now.
+pub fn every<F>(period: Duration, callback: F) -> TimerId
+where
+	F: FnMut() + Send + 'static,
+{
+	let period = core::cmp::max(1, super::duration_to_ticks(period));
+	add_periodic(super::ticks() + period, period, callback)
+}
+
+/// Unregisters a timer. Returns `false` if it already ran (for a one-shot
+/// timer) or was already cancelled.
+pub fn cancel(id: TimerId) -> bool {
+	interrupts::without_interrupts(|| {
+		let mut queue = TIMERS.lock();
+		if Some(id) == queue.running {
+			let cancelled = !queue.running_cancelled;
+			queue.running_cancelled = true;
+			return cancelled;
+		}
+		let length = queue.timers.len();
+		queue.timers.retain(|timer| id != timer.id);
+		length != queue.timers.len()
+	})
+}
+
+/// Timers waiting for their deadline.
+pub fn pending() -> usize {
+	interrupts::without_interrupts(|| TIMERS.lock().timers.len())
+}
+
+/// Runs the callbacks of the timers whose deadline is reached, called by the
+/// timer interrupt handler.
+///
+/// The queue is unlocked while a callback runs, so that it may register or
+/// cancel timers, itself included.
+pub fn run_expired(now: u64) -> () {
+	loop {
+		let mut timer = {
+			let mut queue = TIMERS.lock();
+			match queue.timers.peek() {
+				Some(timer) if timer.deadline <= now => {},
+				_ => return,
+			}
+			let timer = queue.timers.pop().unwrap();
+			queue.running = Some(timer.id);
+			queue.running_cancelled = false;
+			timer
+		};
+		(timer.callback)();
+		let mut queue = TIMERS.lock();
+		if let (Some(period), false) = (timer.period, queue.running_cancelled) {
+			// A late timer catches up on the runs it missed
+			timer.deadline += period;
+			queue.timers.push(timer);
+		}
+		queue.running = None;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use core::sync::atomic::{AtomicU32, Ordering};
+	use super::*;
+
+	#[test_case]
+	fn oneshot_runs_once() {
+		static RUNS: AtomicU32 = AtomicU32::new(0);
+		after(Duration::from_millis(5), || { RUNS.fetch_add(1, Ordering::Relaxed); });
+		crate::time::sleep_ms(20);
+		assert_eq!(1, RUNS.load(Ordering::Relaxed));
+	}
+
+	#[test_case]
+	fn periodic_runs_until_cancelled() {
+		static RUNS: AtomicU32 = AtomicU32::new(0);
+		let id = every(Duration::from_millis(2), || { RUNS.fetch_add(1, Ordering::Relaxed); });
+		crate::time::sleep_ms(20);
+		assert!(cancel(id));
+		let runs = RUNS.load(Ordering::Relaxed);
+		assert!(5 <= runs);
+		crate::time::sleep_ms(10);
+		assert_eq!(runs, RUNS.load(Ordering::Relaxed));
+		assert!(!cancel(id));
+	}
+
+	#[test_case]
+	fn cancelled_oneshot_never_runs() {
+		static RUNS: AtomicU32 = AtomicU32::new(0);
+		let id = after(Duration::from_millis(5), || { RUNS.fetch_add(1, Ordering::Relaxed); });
+		assert!(cancel(id));
+		crate::time::sleep_ms(20);
+		assert_eq!(0, RUNS.load(Ordering::Relaxed));
+	}
+}
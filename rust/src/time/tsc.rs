@@ -0,0 +1,145 @@
+use core::time::Duration;
+use crate::arch::x86::instructions::{self, cpuid, interrupts};
+use crate::arch::x86::pit_8253;
+use super::clocksource::{self, ClockSource};
+
+// https://wiki.osdev.org/TSC
+// https://wiki.osdev.org/Programmable_Interval_Timer#PC_Speaker
+
+/// Length of a calibration run, in oscillator periods of the PIT (10 ms).
+const CALIBRATION_PERIODS: u32 = pit_8253::BASE_FREQUENCY / 100;
+/// The shortest run is kept, the others were probably slowed down by an SMI
+/// or a VM exit.
+const CALIBRATION_RUNS: usize = 3;
+/// Cycles after which a run is given up (about a second at 4 GHz), for the
+/// boards and hypervisors that do not wire the channel 2 output to port 0x61.
+const CALIBRATION_TIMEOUT: u64 = 1 << 32;
+
+/// The time-stamp counter, with the frequency measured by [`init`].
+pub struct TscClock {
+	/// Cycles per second.
+	frequency: u64,
+	/// Counter value at `base`.
+	base_cycles: u64,
+	/// Uptime when the clock was calibrated, so that both clocks agree.
+	base: Duration,
+	invariant: bool,
+}
+
+impl TscClock {
+	pub fn frequency(&self) -> u64 {
+		self.frequency
+	}
+
+	fn cycles_to_duration(&self, cycles: u64) -> Duration {
+		// Split to avoid overflowing: the remainder is below the frequency
+		let nanos = (cycles % self.frequency) * 1_000_000_000 / self.frequency;
+		Duration::new(cycles / self.frequency, nanos as u32)
+	}
+}
+
+impl ClockSource for TscClock {
+	fn name(&self) -> &'static str {
+		"tsc"
+	}
+
+	fn rating(&self) -> u32 {
+		// A TSC whose rate follows the CPU frequency still beats the PIT
+		// while it is not throttled
+		if self.invariant { 300 } else { 200 }
+	}
+
+	fn read(&self) -> Duration {
+		let cycles = instructions::rdtsc().saturating_sub(self.base_cycles);
+		self.base + self.cycles_to_duration(cycles)
+	}
+}
+
+static TSC_CLOCK: spin::Once<TscClock> = spin::Once::new();
+
+/// The calibrated TSC, `None` if the CPU does not have one.
+pub fn clock() -> Option<&'static TscClock> {
+	TSC_CLOCK.get()
+}
+
+/// Counts the TSC cycles elapsing while PIT channel 2 counts down, `None`
+/// if it never expires.
+fn calibrate() -> Option<u64> {
+	let cycles = (0..CALIBRATION_RUNS)
+		.map(|_| {
+			interrupts::without_interrupts(|| {
+				let mut pit = super::_PIT.lock();
+				pit.start_channel_2(CALIBRATION_PERIODS as u16);
+				let start = instructions::rdtsc();
+				while !pit.channel_2_expired() {
+					if instructions::rdtsc() - start > CALIBRATION_TIMEOUT {
+						return None;
+					}
+					core::hint::spin_loop();
+				}
+				Some(instructions::rdtsc() - start)
+			})
+		})
+		.try_fold(u64::MAX, |shortest, run| run.map(|run| shortest.min(run)))?;
+	Some(cycles * pit_8253::BASE_FREQUENCY as u64 / CALIBRATION_PERIODS as u64)
+}
+
+/// Detects the TSC, calibrates it against the PIT and registers it as a
+/// clock source. Must run after the PIT is programmed.
+pub fn init() -> () {
+	if !cpuid::features().contains(cpuid::Features::TSC) {
+		crate::info!("tsc: not supported by the CPU");
+		return;
+	}
+	let frequency = match calibrate() {
+		Some(frequency) => frequency,
+		None => {
+			crate::warn!("tsc: PIT channel 2 never expired, keeping the PIT as clock source");
+			return;
+		},
+	};
+	let clock = TSC_CLOCK.call_once(|| TscClock {
+		frequency,
+		base_cycles: instructions::rdtsc(),
+		base: super::uptime(),
+		invariant: cpuid::has_invariant_tsc(),
+	});
+	crate::info!(
+		"tsc: {}.{:03} MHz{}",
+		frequency / 1_000_000,
+		frequency / 1_000 % 1_000,
+		if clock.invariant { ", invariant" } else { "" }
+	);
+	clocksource::register(clock);
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test_case]
+	fn monotonic_clock_has_sub_tick_resolution() {
+		if clock().is_none() {
+			return;
+		}
+		let start = clocksource::monotonic();
+		let mut end = clocksource::monotonic();
+		while start == end {
+			end = clocksource::monotonic();
+		}
+		assert!(end - start < Duration::from_micros(100));
+	}
+
+	#[test_case]
+	fn tsc_agrees_with_pit() {
+		let Some(tsc) = clock() else {
+			return;
+		};
+		let start = (crate::time::uptime(), tsc.read());
+		crate::time::sleep_ms(50);
+		let pit = crate::time::uptime() - start.0;
+		let tsc = tsc.read() - start.1;
+		// Within two ticks, plus 2% for the calibration
+		assert!(tsc + Duration::from_millis(3) >= pit && pit + Duration::from_millis(3) >= tsc);
+	}
+}
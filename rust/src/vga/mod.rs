@@ -1,9 +1,12 @@
 
-pub mod screen;
-
 use core::fmt::Write;
-use self::screen::Screen;
-use crate::arch::x86::instructions::{interrupts, port::Port};
+use console::screen::{Grid, Screen as ScreenGeneric};
+use crate::arch::x86::instructions::port::Port;
+use crate::sync::{IrqSpinlock, IrqSpinlockGuard};
+
+
+/// A screen drawn straight into the VGA text buffer.
+type Screen = ScreenGeneric<&'static mut Grid>;
 
 lazy_static::lazy_static! {
 	pub static ref _VGA: VGA = VGA::new();
@@ -22,7 +25,7 @@ lazy_static::lazy_static! {
 #[macro_export]
 macro_rules! vga_print {
 	($($arg:tt)*) => {
-		$crate::vga::_write($crate::vga::_VGA.get_current_index(), core::format_args!($($arg)*))
+		$crate::vga::_print(core::format_args!($($arg)*))
 	};
 }
 
@@ -55,14 +58,19 @@ macro_rules! vga_writeln {
 	};
 }
 
+/// Writes to the current screen, which is mirrored on the serial port.
+#[doc(hidden)]
+pub fn _print(args: core::fmt::Arguments) -> core::fmt::Result {
+	let _result: core::fmt::Result = crate::serial::_print(args);
+	_write(crate::vga::_VGA.get_current_index(), args)
+}
+
 #[doc(hidden)]
 pub fn _write(idx: usize, args: core::fmt::Arguments) -> core::fmt::Result {
 	let mut result: core::fmt::Result = Err(core::fmt::Error);
-	interrupts::without_interrupts(|| {
-		if let Some(mut screen) = crate::vga::_VGA.get_screen(idx) {
-			result = screen.write_fmt(args);
-		}
-	});
+	if let Some(mut screen) = crate::vga::_VGA.get_screen(idx) {
+		result = screen.write_fmt(args);
+	}
 	result
 }
 
@@ -78,25 +86,31 @@ macro_rules! vga_input {
 #[doc(hidden)]
 pub fn _input(args: core::fmt::Arguments) -> core::fmt::Result {
 	let mut result: core::fmt::Result = Err(core::fmt::Error);
-	interrupts::without_interrupts(|| {
-		if let Some(mut screen) = crate::vga::_VGA.get_screen(crate::vga::_VGA.get_current_index()) {
-			screen.set_input_mode(true);
-			result = screen.write_fmt(args);
-			screen.set_input_mode(false);
-		}
-	});
+	if let Some(mut screen) = crate::vga::_VGA.get_screen(crate::vga::_VGA.get_current_index()) {
+		screen.set_input_mode(true);
+		result = screen.write_fmt(args);
+		screen.set_input_mode(false);
+	}
 	result
 }
 
-// Printing to a screen requires locking a mutex, that is why
-// it is needed to execute instructions inside a 'without_interrupts' closure
-// (so it avoids deadlocks)
+// ===== Log sink =====
+
+/// Writes log records to the screen of the given index.
+pub struct VgaSink(pub usize);
+
+impl crate::log::Sink for VgaSink {
+	fn write(&self, record: &crate::log::Record) -> () {
+		let _result: core::fmt::Result = crate::vga_writeln!(self.0, "{}", record);
+	}
+}
+
+// Printing to a screen requires locking it, interrupts are disabled
+// meanwhile (so it avoids deadlocks, see `IrqSpinlock`)
 pub fn print_rainbow_42() -> () {
-	interrupts::without_interrupts(|| {
-		if let Some(mut screen) = crate::vga::_VGA.get_screen(crate::vga::_VGA.get_current_index()) {
-			screen.print_rainbow_42();
-		}
-	});
+	if let Some(mut screen) = crate::vga::_VGA.get_screen(crate::vga::_VGA.get_current_index()) {
+		screen.print_rainbow_42();
+	}
 }
 
 #[allow(dead_code)]
@@ -110,6 +124,12 @@ pub enum Command {
 	Clear = 3,
 	Reboot = 4,
 	Shutdown = 5,
+	Dmesg = 6,
+	Uptime = 7,
+	Date = 8,
+	Interrupts = 9,
+	Loadkeys = 10,
+	Kbdrate = 11,
 }
 
 fn cmp(slice: &[u8], command: &[u8]) -> bool {
@@ -122,55 +142,81 @@ fn cmp(slice: &[u8], command: &[u8]) -> bool {
 	i == command.len()
 }
 
-pub fn get_command() -> Command {
-	let mut cmd: Command = Command::None;
-	interrupts::without_interrupts(|| {
-		if let Some(screen) = crate::vga::_VGA.get_screen(crate::vga::_VGA.get_current_index()) {
-			if let Some(slice) = screen.get_input_slice() {
-				if cmp(slice, b"print_rainbow_42") {
-					cmd = Command::Print_rainbow_42;
-				}
-				else if cmp(slice, b"dump_kernel_stack") {
-					cmd = Command::Dump_kernel_stack;
-				}
-				else if cmp(slice, b"clear") {
-					cmd = Command::Clear;
-				}
-				else if cmp(slice, b"reboot") {
-					cmd = Command::Reboot;
+/// Like `cmp`, for a command that takes arguments.
+fn cmp_name(slice: &[u8], command: &[u8]) -> bool {
+	let name_length: usize = command.len() * 2;
+	if slice.len() > name_length {
+		b' ' == slice[name_length] && cmp(&slice[..name_length], command)
+	}
+	else {
+		cmp(slice, command)
+	}
+}
+
+/// Copies the argument `index` of the command being typed (0 for the word
+/// after its name) into `buffer`, truncated to its length.
+pub fn get_argument(index: usize, buffer: &mut [u8]) -> &str {
+	let mut length: usize = 0;
+	if let Some(screen) = crate::vga::_VGA.get_screen(crate::vga::_VGA.get_current_index()) {
+		if let Some(slice) = screen.get_input_slice() {
+			// Words started so far, the name of the command included
+			let mut words: usize = 0;
+			let mut previous: u8 = b' ';
+			for &c in slice.iter().step_by(2) {
+				if b' ' != c && b' ' == previous {
+					words += 1;
 				}
-				else if cmp(slice, b"shutdown") {
-					cmd = Command::Shutdown;
+				if b' ' != c && index + 2 == words && length < buffer.len() {
+					buffer[length] = c;
+					length += 1;
 				}
+				previous = c;
 			}
 		}
-	});
-	cmd
+	}
+	core::str::from_utf8(&buffer[..length]).unwrap_or("")
 }
 
-// ===== Color =====
-
-#[allow(dead_code)]
-#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
-#[repr(u8)]
-pub enum Color {
-	#[default]
-	Black = 0,
-	Blue = 1,
-	Green = 2,
-	Cyan = 3,
-	Red = 4,
-	Magenta = 5,
-	Yellow = 6,
-	White = 7,
-	Black_on_White = 0x70,
-	Blue_on_White = 0x71,
-	Green_on_White = 0x72,
-	Cyan_on_White = 0x73,
-	Red_on_White = 0x74,
-	Magenta_on_White = 0x75,
-	Yellow_on_White = 0x76,
-	White_on_White = 0x77,
+pub fn get_command() -> Command {
+	let mut cmd: Command = Command::None;
+	if let Some(screen) = crate::vga::_VGA.get_screen(crate::vga::_VGA.get_current_index()) {
+		if let Some(slice) = screen.get_input_slice() {
+			if cmp(slice, b"print_rainbow_42") {
+				cmd = Command::Print_rainbow_42;
+			}
+			else if cmp(slice, b"dump_kernel_stack") {
+				cmd = Command::Dump_kernel_stack;
+			}
+			else if cmp(slice, b"clear") {
+				cmd = Command::Clear;
+			}
+			else if cmp(slice, b"reboot") {
+				cmd = Command::Reboot;
+			}
+			else if cmp(slice, b"shutdown") {
+				cmd = Command::Shutdown;
+			}
+			else if cmp(slice, b"dmesg") {
+				cmd = Command::Dmesg;
+			}
+			else if cmp(slice, b"uptime") {
+				cmd = Command::Uptime;
+			}
+			else if cmp(slice, b"date") {
+				cmd = Command::Date;
+			}
+			else if cmp(slice, b"interrupts") {
+				cmd = Command::Interrupts;
+			}
+			else if cmp_name(slice, b"loadkeys") {
+				cmd = Command::Loadkeys;
+			}
+			else if cmp_name(slice, b"kbdrate") {
+				cmd = Command::Kbdrate;
+			}
+		}
+	}
+	cmd
 }
 
 // ===== VGAPorts =====
@@ -191,9 +237,9 @@ struct VGAPorts {
 //#[derive(Debug)]
 pub struct VGA {
 	display: core::sync::atomic::AtomicUsize,
-	screens: [spin::Mutex<Screen>; Self::LENGTH],
+	screens: [IrqSpinlock<Screen>; Self::LENGTH],
 	screen_offset: [usize; Self::LENGTH],
-	ports: spin::Mutex<VGAPorts>,
+	ports: IrqSpinlock<VGAPorts>,
 }
 
 impl VGA {
@@ -203,9 +249,9 @@ impl VGA {
 	pub fn new() -> Self {
 		Self {
 			display: core::sync::atomic::AtomicUsize::new(1),
-			screens: core::array::from_fn(|i| spin::Mutex::new(Screen::new(Self::ADDR + i * Screen::SIZE))),
+			screens: core::array::from_fn(|i| IrqSpinlock::new(Screen::new(unsafe { &mut *((Self::ADDR + i * Screen::SIZE) as *mut Grid) }))),
 			screen_offset: core::array::from_fn(|i| i * Screen::LENGTH),
-			ports: spin::Mutex::new(
+			ports: IrqSpinlock::new(
 				VGAPorts {
 					command: Port::new(VGA_CRTC_INDEX),
 					data: Port::new(VGA_CRTC_DATA),
@@ -214,7 +260,7 @@ impl VGA {
 		}
 	}
 
-	fn get_screen(&self, index: usize) -> Option<spin::MutexGuard<Screen>> {
+	fn get_screen(&self, index: usize) -> Option<IrqSpinlockGuard<'_, Screen>> {
 		if index < Self::LENGTH {
 			Some(self.screens[index].lock())
 		}
@@ -237,7 +283,7 @@ impl VGA {
 		if index < Self::LENGTH {
 			let old_index = self.display.swap(index, core::sync::atomic::Ordering::Relaxed);
 			if index != old_index {
-				let mut ports_guard = self.ports.lock(); // TODO: without_interrupts (unless only keyboard interrupts can trigger this method)
+				let mut ports_guard = self.ports.lock();
 				unsafe {
 					ports_guard.command.write(0x0c);
 					ports_guard.data.write(((self.screen_offset[index] >> 8) & 0xFF) as u8);